@@ -1,7 +1,9 @@
+use std::collections::HashMap;
 use std::io::{self, Write};
-use dynlist::{DynCmd, CmdInfo, DynArg, DObjType, PtrParam};
+use dynlist::{DynCmd, CmdInfo, DynArg, DObjType, PtrParam, FLAG_TABLES, VALUE_TABLES};
+use source::{self, Cursor, ParseError, SourceList, Target, Tok, Value};
 
-const PRELUDE: &'static str = r#"# DynList GNU AS Macros
+const PRELUDE: &str = r#"# DynList GNU AS Macros
 # Bool Types
 .set TRUE, 1
 .set FALSE, 0
@@ -13,15 +15,19 @@ const PRELUDE: &'static str = r#"# DynList GNU AS Macros
     .float \f1, \f2, \f3
 .endm
 "#;
-const BASEMAC: &'static str = "DynListCmd";
+const BASEMAC: &str = "DynListCmd";
 
 /// Write gas macros for all of the dynlist commands. This effectively produces
 /// an include file that can be used to assemble a dynlist
 pub fn write_macros<W: Write>(mut w: W) -> Result<(), io::Error> {
     writeln!(w, "{}", PRELUDE)?;
     write_dobj_constants(&mut w)?;
-    writeln!(w, "")?;
+    writeln!(w)?;
     write_ptrparam_constants(&mut w)?;
+    writeln!(w)?;
+    write_flag_constants(&mut w)?;
+    writeln!(w)?;
+    write_value_constants(&mut w)?;
     writeln!(w, "\n# DynList Command Macros #\n")?;
     for info in DynCmd::variants() {
         writeln!(w, "# {}", info.desc)?;
@@ -31,7 +37,7 @@ pub fn write_macros<W: Write>(mut w: W) -> Result<(), io::Error> {
             (true, _)      => (),
         }
         write_cmd_macro(&mut w, &info)?;
-        write!(w, "\n\n");
+        write!(w, "\n\n")?;
     }
 
     Ok(())
@@ -57,6 +63,32 @@ fn write_ptrparam_constants<W: Write>(w: &mut W) -> Result<(), io::Error> {
     Ok(())
 }
 
+/// Each table of flags, under its description
+#[inline]
+fn write_flag_constants<W: Write>(w: &mut W) -> Result<(), io::Error> {
+    for (i, (desc, names)) in FLAG_TABLES.iter().enumerate() {
+        if i > 0 { writeln!(w)?; }
+        writeln!(w, "# {}", desc)?;
+        for (flag, val) in names.iter() {
+            writeln!(w, ".set {}, {:#x}", flag, val)?;
+        }
+    }
+    Ok(())
+}
+
+/// Each table of named values, under its description
+#[inline]
+fn write_value_constants<W: Write>(w: &mut W) -> Result<(), io::Error> {
+    for (i, (desc, names)) in VALUE_TABLES.iter().enumerate() {
+        if i > 0 { writeln!(w)?; }
+        writeln!(w, "# {}", desc)?;
+        for (name, val) in names.iter() {
+            writeln!(w, ".set {}, {}", name, val)?;
+        }
+    }
+    Ok(())
+}
+
 fn write_cmd_macro<W: Write>(w: &mut W, cmd: &CmdInfo) -> Result<(), io::Error> {
     use self::DynArg::*;
     match cmd.kind {
@@ -110,4 +142,221 @@ r#".macro {} id, flags, type, offset
     {} {}, \id, \type, \offset, \flags
 .endm"#, cmd.base, BASEMAC, cmd.id), 
     }
-}
\ No newline at end of file
+}
+
+/// Directives that only affect layout or linking, and are skipped when parsing
+const IGNORED_DIRECTIVES: &[&str] = &[".include", ".section", ".data", ".rodata", ".text", ".balign", ".align",
+    ".p2align", ".global", ".globl", ".local", ".type", ".size", ".file", ".ident", ".option"];
+/// Directives that put a string after a label
+const STRING_DIRECTIVES: &[&str] = &[".asciz", ".string"];
+
+/// Read the lists out of a gas source written with the macros from `write_macros`,
+/// like those written by `dump` or `--split`.
+///
+/// Each label starts a new list. `.set` (or `=`) constants are resolved, and a label that's
+/// followed by `.asciz` is a string, so using that label as an argument is the same as using
+/// the string itself. Commands can be the per-command macros or the `DynListCmd` base macro,
+/// which may leave out operands (`DynListCmd 7,, 0x10`).
+pub fn parse(text: &str) -> Result<Vec<SourceList>, ParseError> {
+    let toks = source::tokenize(text, true)?;
+    let mut cur = Cursor::new(&toks);
+    let mut lists: Vec<SourceList> = Vec::new();
+    let mut strings: HashMap<String, String> = HashMap::new();
+    // if the last list is still open for commands
+    let mut open = false;
+
+    while cur.peek().is_some() {
+        if end_of_statement(&mut cur) { continue; }
+        let (line, col) = cur.here();
+        let name = cur.ident()?;
+
+        if cur.eat(":") {
+            lists.push(SourceList { name: name.to_string(), cmds: Vec::new() });
+            open = true;
+            continue;
+        }
+        if name == "glabel" {
+            let label = cur.ident()?;
+            lists.push(SourceList { name: label.to_string(), cmds: Vec::new() });
+            open = true;
+        } else if cur.eat("=") {
+            let val = cur.expr()?;
+            cur.consts.insert(name.to_string(), val);
+        } else if name == ".set" || name == ".equ" {
+            let constant = cur.ident()?;
+            // `.set noreorder` and friends are assembler options
+            if cur.eat(",") {
+                let val = cur.expr()?;
+                cur.consts.insert(constant.to_string(), val);
+            }
+        } else if STRING_DIRECTIVES.contains(&name) {
+            let s = match cur.expr()? {
+                Value::Addr(Target::Str(s)) => s,
+                _ => return Err(ParseError::new(line, col, format!("{} needs a string", name))),
+            };
+            match lists.last() {
+                Some(list) if open && list.cmds.is_empty() => {
+                    let label = lists.pop().map(|l| l.name).unwrap_or_default();
+                    strings.insert(label, s);
+                    open = false;
+                },
+                _ => return Err(ParseError::new(line, col, format!("{} needs a label before it", name))),
+            }
+        } else if name == ".macro" {
+            // a macro definition, like the ones from `write_macros`
+            while let Some(tok) = cur.bump() {
+                if tok.tok == Tok::Ident(".endm".to_string()) { break; }
+            }
+        } else if IGNORED_DIRECTIVES.contains(&name) {
+            while !end_of_statement(&mut cur) { cur.bump(); }
+            continue;
+        } else if name.starts_with('.') {
+            return Err(ParseError::new(line, col, format!("unsupported directive `{}`", name)));
+        } else {
+            let cmd = if name == BASEMAC { base_cmd(&mut cur, line, col)? } else {
+                let args = macro_args(&mut cur)?.into_iter()
+                    .map(|arg| arg.ok_or_else(|| ParseError::new(line, col, format!("{} is missing an argument", name))))
+                    .collect::<Result<Vec<_>, _>>()?;
+                source::encode(name, args, line, col)?
+            };
+            if !open {
+                lists.push(SourceList { name: "list_00000000".to_string(), cmds: Vec::new() });
+                open = true;
+            }
+            if let Some(list) = lists.last_mut() { list.cmds.push(cmd); }
+        }
+        if !end_of_statement(&mut cur) {
+            return Err(cur.error(format!("expected the end of the line, found {}", cur.describe())));
+        }
+    }
+
+    // labels of strings stand for the strings themselves
+    for reloc in lists.iter_mut().flat_map(|l| l.cmds.iter_mut()).flat_map(|c| c.relocs.iter_mut()) {
+        let string = match reloc.target {
+            Target::Sym(ref label) => strings.get(label).cloned(),
+            Target::Str(_) => None,
+        };
+        if let Some(s) = string { reloc.target = Target::Str(s); }
+    }
+    Ok(lists)
+}
+
+/// Take the end of a line or `;`, if that's next
+fn end_of_statement(cur: &mut Cursor) -> bool {
+    match cur.peek() {
+        None => true,
+        Some(Tok::Newline) | Some(Tok::Punct(";")) => {
+            cur.bump();
+            true
+        },
+        _ => false,
+    }
+}
+
+/// Comma separated macro arguments, up to the end of the statement. Left out arguments are `None`.
+fn macro_args(cur: &mut Cursor) -> Result<Vec<Option<Value>>, ParseError> {
+    let mut args = Vec::new();
+    let at_end = |cur: &Cursor| matches!(cur.peek(), None | Some(Tok::Newline) | Some(Tok::Punct(";")));
+    if at_end(cur) { return Ok(args); }
+    loop {
+        let arg = match cur.peek() {
+            Some(Tok::Punct(",")) => None,
+            _ if at_end(cur) => None,
+            _ => Some(cur.expr()?),
+        };
+        args.push(arg);
+        if !cur.eat(",") { return Ok(args); }
+    }
+}
+
+/// `DynListCmd cmd, w1=0, w2=0, f1=0.0, f2=0.0, f3=0.0`
+fn base_cmd(cur: &mut Cursor, line: usize, col: usize) -> Result<source::SourceCmd, ParseError> {
+    let args = macro_args(cur)?;
+    if args.len() > 6 {
+        return Err(ParseError::new(line, col, format!("{} takes at most 6 arguments, but {} were given", BASEMAC, args.len())));
+    }
+    if let None | Some(None) = args.first() {
+        return Err(ParseError::new(line, col, format!("{} needs a command number", BASEMAC)));
+    }
+    let mut words = [None, None, None, None, None, None];
+    for (word, arg) in words.iter_mut().zip(args) {
+        *word = arg;
+    }
+    source::encode_words(words, line, col)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dump::{self, Config};
+    use dynlist::{DynId, DynListItem, Ptr, Vector};
+    use dynlist::DynCmd::*;
+    use source::Reloc;
+
+    #[test]
+    fn dumped_list_parses_back() {
+        let cmds = vec![
+            Start,
+            MakeObj(DObjType::D_NET, DynId(1)),
+            SetHeaderFlag(0x06),
+            SetScale(Vector { x: 1.5, y: -2.0, z: 0.25 }),
+            ParamPtr(PtrParam::PARAM_OBJ_VTX, Ptr(0x8017_0000)),
+            MakeValPtr(DynId(4), 1.0, 2, 8.0),
+            Stop,
+        ];
+        let expected: Vec<[u32; 6]> = cmds.iter().map(|c| c.to_struct()).collect();
+        let items = cmds.into_iter().map(|c| Ok(DynListItem::from_cmd(c)));
+        let cfg = Config { label: Some("dynlist_net".to_string()), ..Config::default() };
+        let mut text = Vec::new();
+        dump::gas(&mut text, items, &cfg).unwrap();
+
+        let lists = parse(&String::from_utf8(text).unwrap()).unwrap();
+        assert_eq!(lists.len(), 1);
+        assert_eq!(lists[0].name, "dynlist_net");
+        let raw: Vec<[u32; 6]> = lists[0].cmds.iter().map(|c| c.raw).collect();
+        assert_eq!(raw, expected);
+    }
+
+    #[test]
+    fn list_after_the_macros() {
+        let mut text = Vec::new();
+        write_macros(&mut text).unwrap();
+        let text = String::from_utf8(text).unwrap() + r#"
+.section .data
+.set JAW_FLAGS, OBJ_INVISIBLE | OBJ_PICKED
+str_jaw:
+    .asciz "jaw"
+glabel dynlist_jaw
+    StartList
+    MakeDynObj D_JOINT, str_jaw  # the jaw
+    SetHeaderFlag JAW_FLAGS; SetParamPtr PARAM_OBJ_VTX, vtx_jaw
+    DynListCmd 7,, 0x10
+    StopList
+other = 3
+list_2:
+    SetColourNum other
+"#;
+        let lists = parse(&text).unwrap();
+        let names: Vec<&str> = lists.iter().map(|l| l.name.as_str()).collect();
+        assert_eq!(names, ["dynlist_jaw", "list_2"]);
+        let cmds = &lists[0].cmds;
+        assert_eq!(cmds.len(), 6);
+        assert_eq!(cmds[1].relocs, [Reloc { word: 1, target: Target::Str("jaw".to_string()) }]);
+        assert_eq!(cmds[2].raw, [7, 0, 0x06, 0, 0, 0]);
+        assert_eq!(cmds[3].relocs, [Reloc { word: 1, target: Target::Sym("vtx_jaw".to_string()) }]);
+        assert_eq!(cmds[4].raw, [7, 0, 0x10, 0, 0, 0]);
+        assert_eq!(lists[1].cmds[0].raw, [13, 0, 3, 0, 0, 0]);
+    }
+
+    #[test]
+    fn errors_have_their_position() {
+        let err = parse("l:\n    StartList\n    SetScale 1.0, 2.0\n").unwrap_err();
+        assert_eq!((err.line, err.col), (3, 5));
+        let err = parse("l:\n    StopList 1\n").unwrap_err();
+        assert_eq!(err.line, 2);
+        let err = parse("    .asciz \"jaw\"\n").unwrap_err();
+        assert!(err.msg.contains("needs a label"), "{}", err.msg);
+        let err = parse("    .word 4\n").unwrap_err();
+        assert!(err.msg.contains("unsupported directive"), "{}", err.msg);
+    }
+}
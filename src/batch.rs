@@ -0,0 +1,241 @@
+//! Manifests of many dynlists to dump from the same ROM, as either TOML:
+//! ```toml
+//! rom = "baserom.us.z64"
+//! symbols = "undefined_syms.txt"
+//! base_vram = 0x8016F000
+//! base_offset = 0x21F4C0
+//! format = "gas"
+//!
+//! [[list]]
+//! name = "dynlist_mario_face"
+//! offset = 0x2A3B40
+//! output = "asm/mario_face.s"
+//!
+//! [[list]]
+//! name = "dynlist_eye"
+//! symbol = "D_801A5B2C"
+//! format = "c"
+//! ```
+//! or CSV, with a `name,offset,symbol,output,format` header and one list per row.
+//! The top level settings of a TOML manifest can be given on the command line for CSV.
+//!
+//! Symbols are read from `name = 0xADDR;` lines, like a linker script, and are turned into
+//! file offsets with `addr - base_vram + base_offset`.
+
+use csv;
+use dump::{self, Format};
+use dynlist::{self, Confidence, DynListIter, Limits, CMD_SIZE};
+use error::Error;
+use split;
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, BufWriter, Read, Seek};
+use std::path::{Path, PathBuf};
+use toml;
+
+/// A number, or text that might be a number or a symbol name
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+enum Addr {
+    Int(u64),
+    Text(String),
+}
+
+/// One list in a manifest
+#[derive(Debug, Clone, Deserialize)]
+pub struct Entry {
+    /// Label for the list, and the default output file name
+    pub name: String,
+    offset: Option<Addr>,
+    symbol: Option<String>,
+    pub output: Option<PathBuf>,
+    format: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct Manifest {
+    pub rom: Option<PathBuf>,
+    pub symbols: Option<PathBuf>,
+    pub base_vram: Option<u64>,
+    pub base_offset: Option<u64>,
+    /// Format for lists that don't give their own
+    pub format: Option<String>,
+    #[serde(rename = "list", default)]
+    pub lists: Vec<Entry>,
+    /// Directory that relative paths in the manifest start from
+    #[serde(skip)]
+    pub dir: PathBuf,
+}
+
+/// A manifest entry with its offset, output, and format worked out
+#[derive(Debug, Clone)]
+pub struct Job {
+    pub name: String,
+    pub offset: u64,
+    pub output: PathBuf,
+    pub format: Format,
+}
+
+impl Manifest {
+    /// Load a `.toml` or `.csv` manifest
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let mut text = String::new();
+        File::open(path)
+            .and_then(|mut f| f.read_to_string(&mut text))
+            .map_err(|err| Error::File { path: path.display().to_string(), err })?;
+        let is_csv = path.extension().map(|e| e == "csv").unwrap_or(false);
+        let mut manifest = if is_csv {
+            Manifest::from_csv(text.as_bytes())?
+        } else {
+            Manifest::from_toml(&text)?
+        };
+        manifest.dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        Ok(manifest)
+    }
+
+    pub fn from_toml(text: &str) -> Result<Self, Error> {
+        toml::from_str(text).map_err(|e| Error::Syntax { what: "TOML manifest", line: None, msg: e.to_string() })
+    }
+
+    pub fn from_csv<R: Read>(rdr: R) -> Result<Self, Error> {
+        let mut lists = Vec::new();
+        for (i, row) in csv::Reader::from_reader(rdr).deserialize().enumerate() {
+            // the header is line 1
+            lists.push(row.map_err(|e| Error::Syntax { what: "CSV manifest", line: Some(i + 2), msg: e.to_string() })?);
+        }
+        Ok(Manifest { lists, ..Manifest::default() })
+    }
+
+    /// A path from the manifest, relative to the manifest's directory
+    pub fn path(&self, path: &Path) -> PathBuf {
+        self.dir.join(path)
+    }
+
+    /// Work out where a list is and how it should be written
+    pub fn job(&self, entry: &Entry, symbols: &HashMap<String, u64>) -> Result<Job, Error> {
+        let bad = |msg: String| Error::Manifest { msg };
+        let symbol_offset = |name: &str| -> Result<u64, Error> {
+            let addr = *symbols.get(name).ok_or_else(|| bad(format!("unknown symbol {}", name)))?;
+            let base_vram = self.base_vram.unwrap_or(0);
+            if addr < base_vram {
+                return Err(bad(format!("symbol {} ({:#X}) is below base_vram {:#X}", name, addr, base_vram)));
+            }
+            Ok(addr - base_vram + self.base_offset.unwrap_or(0))
+        };
+        let offset = match (&entry.offset, &entry.symbol) {
+            (Some(_), Some(_)) => return Err(bad("list has both an offset and a symbol".to_string())),
+            (Some(Addr::Int(n)), None) => *n,
+            (Some(Addr::Text(t)), None) => match parse_u64(t) {
+                Some(n) => n,
+                None => symbol_offset(t)?,
+            },
+            (None, Some(sym)) => symbol_offset(sym)?,
+            (None, None) => return Err(bad("list needs an offset or a symbol".to_string())),
+        };
+        let format = match entry.format.as_ref().or(self.format.as_ref()) {
+            Some(f) => f.parse().map_err(bad)?,
+            None => Format::Gas,
+        };
+        let output = match entry.output {
+            Some(ref out) => self.path(out),
+            None => self.path(Path::new(&format!("{}.{}", entry.name, format.extension()))),
+        };
+        Ok(Job { name: entry.name.clone(), offset, output, format })
+    }
+}
+
+/// How dumping one list from a manifest went
+#[derive(Debug)]
+pub struct Outcome {
+    /// Name of the manifest entry
+    pub name: String,
+    /// Where the list starts, and how much that looks like a dynlist, if it could be found
+    pub probe: Option<(u64, Confidence)>,
+    /// Where the list was written, and how many commands it has
+    pub result: Result<(Job, usize), Error>,
+}
+
+/// Dump every list in `manifest` out of `rdr`, or write them all into `split` as a decomp-style
+/// split, with its headers. A list that can't be dumped doesn't stop the rest.
+pub fn dump_all<R>(manifest: &Manifest, mut rdr: R, symbols: &HashMap<String, u64>, limits: Limits, split: Option<&Path>) -> Result<Vec<Outcome>, Error>
+    where R: Read + Seek
+{
+    let mut outcomes = Vec::new();
+    let mut split_lists = Vec::new();
+    for entry in &manifest.lists {
+        let mut probe = None;
+        let result = manifest.job(entry, symbols).and_then(|mut job| {
+            probe = Some((job.offset, dynlist::probe(&mut rdr, job.offset)?));
+            let cfg = dump::Config { label: Some(job.name.clone()), ..dump::Config::new(job.offset) };
+            if let Some(dir) = split {
+                let list = split::write_list(&mut rdr, dir, &cfg, job.format, limits)?;
+                job.output = dir.join(list.file_name());
+                let count = (list.end - list.start) / CMD_SIZE;
+                split_lists.push(list);
+                return Ok((job, count as usize));
+            }
+            let count = dump_one(&mut rdr, &job, &cfg, limits)?;
+            Ok((job, count))
+        });
+        outcomes.push(Outcome { name: entry.name.clone(), probe, result });
+    }
+    if let Some(dir) = split {
+        split::write_support_files(dir, &split_lists)
+            .map_err(|err| Error::File { path: dir.display().to_string(), err })?;
+    }
+    Ok(outcomes)
+}
+
+/// Write one list to its job's output, and count its commands. The output is removed if the list
+/// can't be written, so half of a list isn't left behind.
+fn dump_one<R: Read + Seek>(rdr: R, job: &Job, cfg: &dump::Config, limits: Limits) -> Result<usize, Error> {
+    let file_err = |err| Error::File { path: job.output.display().to_string(), err };
+    let count = Cell::new(0);
+    let dynlist = DynListIter::from_reader(rdr, job.offset)?
+        .limits(limits)
+        .inspect(|_| count.set(count.get() + 1));
+    if let Some(dir) = job.output.parent() {
+        fs::create_dir_all(dir).map_err(file_err)?;
+    }
+    let wtr = File::create(&job.output).map(BufWriter::new).map_err(file_err)?;
+    if let Err(e) = dump::write(job.format, wtr, dynlist, cfg) {
+        let _ = fs::remove_file(&job.output);
+        return Err(e);
+    }
+    Ok(count.get())
+}
+
+/// Read a file of `name = value;` symbol definitions
+pub fn load_symbols(path: &Path) -> Result<HashMap<String, u64>, Error> {
+    let f = File::open(path).map_err(|err| Error::File { path: path.display().to_string(), err })?;
+    read_symbols(BufReader::new(f))
+}
+
+/// Read `name = value;` symbol definitions, skipping blank lines and comments
+pub fn read_symbols<R: BufRead>(rdr: R) -> Result<HashMap<String, u64>, Error> {
+    let mut symbols = HashMap::new();
+    for (i, line) in rdr.lines().enumerate() {
+        let line = line?;
+        let line = line.split("//").next().unwrap_or_default();
+        let line = line.split('#').next().unwrap_or_default().trim();
+        if line.is_empty() { continue; }
+        let syntax = |msg: String| Error::Syntax { what: "symbol file", line: Some(i + 1), msg };
+        let mut parts = line.trim_end_matches(';').splitn(2, '=');
+        let (name, value) = match (parts.next(), parts.next()) {
+            (Some(name), Some(value)) => (name.trim(), value.trim()),
+            _ => return Err(syntax(format!("expected `name = value;`, found `{}`", line))),
+        };
+        let value = parse_u64(value)
+            .ok_or_else(|| syntax(format!("bad value `{}` for symbol {}", value, name)))?;
+        symbols.insert(name.to_string(), value);
+    }
+    Ok(symbols)
+}
+
+fn parse_u64(s: &str) -> Option<u64> {
+    match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => s.parse().ok(),
+    }
+}
@@ -1,5 +1,6 @@
 use std::io::{self, Write};
-use dynlist::{DynCmd, CmdInfo, DynArg, DObjType, PtrParam};
+use dynlist::{DynCmd, CmdInfo, DynArg, DObjType, PtrParam, FLAG_TABLES, VALUE_TABLES};
+use source::{self, Cursor, ParseError, SourceList, Tok};
 
 macro_rules! cmd_type_name {
     () => ( "DynListCmd" )
@@ -7,12 +8,12 @@ macro_rules! cmd_type_name {
 macro_rules! header_name {
     () => ( "_DYN_LIST_MACROS_H_")
 }
-pub const PREFIX: &'static str = "";
-pub const STRUCT_NAME: &'static str = concat!("struct ", cmd_type_name!());
+pub const PREFIX: &str = "";
+pub const STRUCT_NAME: &str = concat!("struct ", cmd_type_name!());
 
-const IFGUARD_START: &'static str = concat!("#ifndef ", header_name![], "\n#define ", header_name![]);
-const IFGUARD_END: &'static str = concat!("#endif /* ", header_name![], " */");
-const STRUCT_DEC: &'static str = concat!("struct ", cmd_type_name!(), r#" {
+const IFGUARD_START: &str = concat!("#ifndef ", header_name![], "\n#define ", header_name![]);
+const IFGUARD_END: &str = concat!("#endif /* ", header_name![], " */");
+const STRUCT_DEC: &str = concat!("struct ", cmd_type_name!(), r#" {
     int cmd;
     union { void *ptr; int word; } w1;
     union { void *ptr; int word; } w2;
@@ -23,11 +24,15 @@ pub fn write_header<W: Write>(mut w: W) -> Result<(), io::Error> {
     writeln!(w, "{}\n", IFGUARD_START)?;
     writeln!(w, "{}\n", STRUCT_DEC)?;
     write_dobj_defines(&mut w)?;
-    write!(w, "\n")?;
+    writeln!(w)?;
     write_ptrparam_constants(&mut w)?;
-    write!(w, "\n")?;
+    writeln!(w)?;
+    write_flag_constants(&mut w)?;
+    writeln!(w)?;
+    write_value_constants(&mut w)?;
+    writeln!(w)?;
 
-    writeln!(w, "/* {} Macros */", cmd_type_name![]);
+    writeln!(w, "/* {} Macros */", cmd_type_name![])?;
     for info in DynCmd::variants() {
         writeln!(w, "/* {} */", info.desc)?;
         match (info.objs.is_empty(), info.objs.is_all()) {
@@ -36,7 +41,7 @@ pub fn write_header<W: Write>(mut w: W) -> Result<(), io::Error> {
             (true, _)      => (),
         }
         write_cmd_macros(&mut w, &info)?;
-        write!(w, "\n");
+        writeln!(w)?;
     }
     writeln!(w, "{}", IFGUARD_END)?;
     Ok(())
@@ -60,6 +65,30 @@ fn write_ptrparam_constants<W: Write>(w: &mut W) -> Result<(), io::Error> {
     Ok(())
 }
 
+#[inline]
+fn write_flag_constants<W: Write>(w: &mut W) -> Result<(), io::Error> {
+    for (i, (desc, names)) in FLAG_TABLES.iter().enumerate() {
+        if i > 0 { writeln!(w)?; }
+        writeln!(w, "/* {} */", desc)?;
+        for (flag, val) in names.iter() {
+            writeln!(w, "#define {} {:#x}", flag, val)?;
+        }
+    }
+    Ok(())
+}
+
+#[inline]
+fn write_value_constants<W: Write>(w: &mut W) -> Result<(), io::Error> {
+    for (i, (desc, names)) in VALUE_TABLES.iter().enumerate() {
+        if i > 0 { writeln!(w)?; }
+        writeln!(w, "/* {} */", desc)?;
+        for (name, val) in names.iter() {
+            writeln!(w, "#define {} {}", name, val)?;
+        }
+    }
+    Ok(())
+}
+
 /* In C, unlike ASM, we can't rely on default arguments (beyond what's expected for struct init) */ 
 fn write_cmd_macros<W: Write>(w: &mut W, cmd: &CmdInfo) -> Result<(), io::Error> {
     use self::DynArg::*;
@@ -114,4 +143,131 @@ r#"#define {}{}(id, flags, type, offset) \
     {{ {}, {{(void *)(id)}}, {{(void *)(type)}}, {{(offset), (flags), 0.0}} }}"#, 
             PREFIX, cmd.base, cmd.id),
     }
-}
\ No newline at end of file
+}
+/// Struct names that a list array can be declared with: ours, and the decomp's
+const LIST_TYPES: &[&str] = &[cmd_type_name!(), "DynList"];
+
+/// Read every `struct DynListCmd name[] = { ... };` array out of a C source file, like the ones
+/// in the SM64 decomp (which use `struct DynList`) or those written by `dump -f c`. Other
+/// declarations are skipped, as are preprocessor lines, so the file doesn't need to be run
+/// through `cpp` first.
+pub fn parse(text: &str) -> Result<Vec<SourceList>, ParseError> {
+    let toks = source::tokenize(text, false)?;
+    let mut cur = Cursor::new(&toks);
+    let mut lists = Vec::new();
+    while cur.peek().is_some() {
+        let start = cur.pos;
+        match (cur.peek(), cur.peek_at(1), cur.peek_at(2)) {
+            (Some(Tok::Ident(ty)), Some(Tok::Ident(name)), Some(Tok::Punct("[")))
+                if LIST_TYPES.contains(&ty.as_str()) => {
+                cur.pos += 3;
+                // an optional array length
+                if let Some(Tok::Num(_)) = cur.peek() { cur.pos += 1; }
+                if cur.eat("]") && cur.eat("=") && cur.eat("{") {
+                    lists.push(parse_list(&mut cur, name)?);
+                    continue;
+                }
+                cur.pos = start + 1;
+            },
+            _ => cur.pos += 1,
+        }
+    }
+    Ok(lists)
+}
+
+/// Parse the macro calls of an array, up to and including the closing brace
+fn parse_list(cur: &mut Cursor, name: &str) -> Result<SourceList, ParseError> {
+    let mut list = SourceList { name: name.to_string(), cmds: Vec::new() };
+    while !cur.eat("}") {
+        let (line, col) = cur.here();
+        let cmd = cur.ident()?;
+        cur.expect("(")?;
+        let mut args = Vec::new();
+        if !cur.eat(")") {
+            loop {
+                args.push(cur.expr()?);
+                if cur.eat(")") { break; }
+                cur.expect(",")?;
+            }
+        }
+        list.cmds.push(source::encode(cmd, args, line, col)?);
+        if !cur.eat(",") {
+            cur.expect("}")?;
+            break;
+        }
+    }
+    Ok(list)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dump::{self, Config};
+    use dynlist::{DynId, DynListItem, Ptr, Vector};
+    use dynlist::DynCmd::*;
+    use source::{Reloc, Target};
+
+    #[test]
+    fn dumped_list_parses_back() {
+        let cmds = vec![
+            Start,
+            MakeObj(DObjType::D_NET, DynId(1)),
+            SetHeaderFlag(0x06),
+            SetScale(Vector { x: 1.5, y: -2.0, z: 0.25 }),
+            ParamPtr(PtrParam::PARAM_OBJ_VTX, Ptr(0x8017_0000)),
+            MakeValPtr(DynId(4), 1.0, 2, 8.0),
+            Stop,
+        ];
+        let expected: Vec<[u32; 6]> = cmds.iter().map(|c| c.to_struct()).collect();
+        let items = cmds.into_iter().map(|c| Ok(DynListItem::from_cmd(c)));
+        let cfg = Config { label: Some("dynlist_net".to_string()), ..Config::default() };
+        let mut text = Vec::new();
+        dump::c(&mut text, items, &cfg).unwrap();
+
+        let lists = parse(&String::from_utf8(text).unwrap()).unwrap();
+        assert_eq!(lists.len(), 1);
+        assert_eq!(lists[0].name, "dynlist_net");
+        let raw: Vec<[u32; 6]> = lists[0].cmds.iter().map(|c| c.raw).collect();
+        assert_eq!(raw, expected);
+    }
+
+    #[test]
+    fn decomp_list_after_the_header() {
+        let mut text = Vec::new();
+        write_header(&mut text).unwrap();
+        let text = String::from_utf8(text).unwrap() + r#"
+static s32 sUnused = 4;
+/* the jaw */
+struct DynList dynlist_jaw[] = {
+    StartList(),
+    MakeDynObj(D_JOINT, "jaw" "_1"),
+    SetHeaderFlag(OBJ_INVISIBLE | OBJ_PICKED),
+    SetParamPtr(PARAM_OBJ_VTX, &vtx_jaw),
+    SetParamF(PARAM_F_ALPHA, 0.5f),
+    JumpToList(dynlist_teeth),
+    StopList(),
+};
+"#;
+        let lists = parse(&text).unwrap();
+        assert_eq!(lists.len(), 1);
+        let cmds = &lists[0].cmds;
+        assert_eq!(cmds.len(), 7);
+        assert_eq!(cmds[1].raw, [15, 0, DObjType::D_JOINT as u32, 0, 0, 0]);
+        assert_eq!(cmds[1].relocs, [Reloc { word: 1, target: Target::Str("jaw_1".to_string()) }]);
+        assert_eq!(cmds[2].raw, [7, 0, 0x06, 0, 0, 0]);
+        assert_eq!(cmds[3].raw[2], PtrParam::PARAM_OBJ_VTX as u32);
+        assert_eq!(cmds[3].relocs, [Reloc { word: 1, target: Target::Sym("vtx_jaw".to_string()) }]);
+        assert_eq!(f32::from_bits(cmds[4].raw[3]), 0.5);
+        assert_eq!(cmds[5].relocs, [Reloc { word: 1, target: Target::Sym("dynlist_teeth".to_string()) }]);
+        // the line of each command is kept
+        assert_eq!(text.lines().nth(cmds[0].line - 1).map(str::trim), Some("StartList(),"));
+    }
+
+    #[test]
+    fn errors_have_their_position() {
+        let err = parse("struct DynList l[] = {\n    StartList(),\n    SetScale(1.0, 2.0),\n};\n").unwrap_err();
+        assert_eq!((err.line, err.col), (3, 5));
+        let err = parse("struct DynList l[] = {\n    StartList(\n").unwrap_err();
+        assert_eq!(err.line, 2);
+    }
+}
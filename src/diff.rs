@@ -0,0 +1,508 @@
+//! Diffs of two dynlists, either command by command or object by object.
+//!
+//! For commands, the lists are aligned with a longest common subsequence. Commands that
+//! only exist in one list are added or removed, and a removed command next to an added command
+//! of the same kind is reported as changed, along with the arguments that changed.
+//!
+//! For objects, both lists are interpreted into a `Scene`, and objects are matched by their id.
+//! Anonymous objects (like vertices) are matched by type and order within their named group,
+//! so lists that build the same objects in a different command order come out the same.
+
+use dynlist::{ArgVal, DObjType, DynCmd, DynId, Vector, CMD_SIZE};
+use scene::{Obj, Scene};
+use std::collections::{BTreeSet, HashMap};
+use std::io::{self, Write};
+
+/// How a command in one list relates to the other list
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Edit {
+    /// Indices of the same command in the old and new lists
+    Same(usize, usize),
+    Removed(usize),
+    Added(usize),
+    /// The same kind of command with different arguments
+    Changed(usize, usize),
+}
+
+/// One side of a diff
+#[derive(Debug, Clone)]
+pub struct List {
+    pub label: String,
+    /// File offset of the first command
+    pub address: u64,
+    pub cmds: Vec<DynCmd>,
+}
+
+/// Counts of each kind of edit
+#[derive(Debug, Default, Copy, Clone)]
+pub struct Summary {
+    pub same: usize,
+    pub changed: usize,
+    pub added: usize,
+    pub removed: usize,
+}
+
+impl Summary {
+    pub fn is_same(&self) -> bool {
+        self.changed + self.added + self.removed == 0
+    }
+}
+
+/// Align two lists of commands
+pub fn align(old: &[DynCmd], new: &[DynCmd]) -> Vec<Edit> {
+    // compare the encoded words, so that NaN floats are still equal to themselves
+    let old_words: Vec<[u32; 6]> = old.iter().map(|c| c.to_struct()).collect();
+    let new_words: Vec<[u32; 6]> = new.iter().map(|c| c.to_struct()).collect();
+    let mut edits = Vec::with_capacity(old.len().max(new.len()));
+    let mut removed = Vec::new();
+    let mut added = Vec::new();
+    for (o, n) in lcs(&old_words, &new_words) {
+        match (o, n) {
+            (Some(o), Some(n)) => {
+                pair_changes(&old_words, &new_words, &mut removed, &mut added, &mut edits);
+                edits.push(Edit::Same(o, n));
+            },
+            (Some(o), None) => removed.push(o),
+            (None, Some(n)) => added.push(n),
+            (None, None) => unreachable!(),
+        }
+    }
+    pair_changes(&old_words, &new_words, &mut removed, &mut added, &mut edits);
+    edits
+}
+
+/// Pair up the removed and added commands between two matches with the same command id
+fn pair_changes(old: &[[u32; 6]], new: &[[u32; 6]], removed: &mut Vec<usize>, added: &mut Vec<usize>, edits: &mut Vec<Edit>) {
+    let old_ids: Vec<u32> = removed.iter().map(|&i| old[i][0]).collect();
+    let new_ids: Vec<u32> = added.iter().map(|&i| new[i][0]).collect();
+    for step in lcs(&old_ids, &new_ids) {
+        edits.push(match step {
+            (Some(o), Some(n)) => Edit::Changed(removed[o], added[n]),
+            (Some(o), None) => Edit::Removed(removed[o]),
+            (None, Some(n)) => Edit::Added(added[n]),
+            (None, None) => unreachable!(),
+        });
+    }
+    removed.clear();
+    added.clear();
+}
+
+/// Walk through a longest common subsequence of `a` and `b`. Each step is a pair of
+/// matching indices, or an index that only one side has. Removals come before additions.
+fn lcs<T: PartialEq>(a: &[T], b: &[T]) -> Vec<(Option<usize>, Option<usize>)> {
+    // most edits are small, so only the middle of the lists needs to be searched
+    let prefix = a.iter().zip(b).take_while(|(x, y)| x == y).count();
+    let suffix = a[prefix..].iter().rev().zip(b[prefix..].iter().rev()).take_while(|(x, y)| x == y).count();
+    let (a_mid, b_mid) = (&a[prefix..a.len() - suffix], &b[prefix..b.len() - suffix]);
+
+    let mut mid = Vec::with_capacity(a_mid.len() + b_mid.len());
+    hirschberg(a_mid, b_mid, prefix, prefix, &mut mid);
+
+    let mut steps: Vec<_> = (0..prefix).map(|i| (Some(i), Some(i))).collect();
+    // the halves are solved separately, so put the removals of each gap back in front
+    let mut added = Vec::new();
+    for step in mid {
+        match step {
+            (Some(_), None) => steps.push(step),
+            (None, Some(_)) => added.push(step),
+            _ => {
+                steps.append(&mut added);
+                steps.push(step);
+            },
+        }
+    }
+    steps.append(&mut added);
+    steps.extend((0..suffix).map(|k| (Some(a.len() - suffix + k), Some(b.len() - suffix + k))));
+    steps
+}
+
+/// Hirschberg's algorithm: split `a` in half, find where the halves' LCSes meet in `b`,
+/// and recurse. This only keeps two rows of the LCS table at a time, so two long lists
+/// with a lot of differences don't need an `a.len() * b.len()` table.
+/// `ai` and `bj` are the indices of `a` and `b` in the whole lists.
+fn hirschberg<T: PartialEq>(a: &[T], b: &[T], ai: usize, bj: usize, out: &mut Vec<(Option<usize>, Option<usize>)>) {
+    if a.len() <= 1 {
+        let matched = a.first().and_then(|x| b.iter().position(|y| x == y));
+        if matched.is_none() && !a.is_empty() {
+            out.push((Some(ai), None));
+        }
+        for j in 0..b.len() {
+            out.push(if matched == Some(j) { (Some(ai), Some(bj + j)) } else { (None, Some(bj + j)) });
+        }
+        return;
+    }
+    let half = a.len() / 2;
+    let head = lcs_row(&a[..half], b, false);
+    let tail = lcs_row(&a[half..], b, true);
+    let m = b.len();
+    let split = (0..=m).max_by_key(|&j| (head[j] + tail[m - j], std::cmp::Reverse(j))).unwrap_or(0);
+    hirschberg(&a[..half], &b[..split], ai, bj, out);
+    hirschberg(&a[half..], &b[split..], ai + half, bj + split, out);
+}
+
+/// The last row of the LCS table of `a` and `b`: the length of the LCS of `a` and each prefix
+/// of `b`. If `rev` is set, both lists are read backwards, so it's for each suffix of `b`.
+fn lcs_row<T: PartialEq>(a: &[T], b: &[T], rev: bool) -> Vec<u32> {
+    let (n, m) = (a.len(), b.len());
+    let mut prev = vec![0u32; m + 1];
+    let mut cur = vec![0u32; m + 1];
+    for i in 0..n {
+        let x = if rev { &a[n - 1 - i] } else { &a[i] };
+        for j in 1..=m {
+            let y = if rev { &b[m - j] } else { &b[j - 1] };
+            cur[j] = if x == y { prev[j - 1] + 1 } else { prev[j].max(cur[j - 1]) };
+        }
+        std::mem::swap(&mut prev, &mut cur);
+    }
+    prev
+}
+
+/// Describe the arguments that differ between two commands of the same kind,
+/// down to a single vector component or flag bit
+pub fn arg_changes(old: &DynCmd, new: &DynCmd) -> Vec<String> {
+    let params = old.info().kind.params();
+    let mut names = params.iter();
+    let mut changes = Vec::new();
+    for (a, b) in old.args().iter().zip(new.args().iter()) {
+        let count = a.words().len();
+        let arg_names: Vec<&str> = names.by_ref().take(count).cloned().collect();
+        let name = arg_names.first().cloned().unwrap_or("arg");
+        if a.words() == b.words() { continue; }
+        match (a, b) {
+            (ArgVal::Vec(va), ArgVal::Vec(vb)) => {
+                let pairs = [(va.x, vb.x), (va.y, vb.y), (va.z, vb.z)];
+                for (n, &(x, y)) in arg_names.iter().zip(pairs.iter()) {
+                    if x.to_bits() != y.to_bits() {
+                        changes.push(format!("{}: {:?} -> {:?}", n, x, y));
+                    }
+                }
+            },
+            (ArgVal::Flag(fa), ArgVal::Flag(fb)) => changes.push(format!("{}: {}", name, flag_change(*fa, *fb))),
+            _ => changes.push(format!("{}: {} -> {}", name, show(a), show(b))),
+        }
+    }
+    changes
+}
+
+/// Old and new flags, and which bits were set and cleared
+fn flag_change(old: u32, new: u32) -> String {
+    let mut bits = Vec::new();
+    if new & !old != 0 { bits.push(format!("set {:#x}", new & !old)); }
+    if old & !new != 0 { bits.push(format!("cleared {:#x}", old & !new)); }
+    format!("{:#x} -> {:#x} ({})", old, new, bits.join(", "))
+}
+
+fn show(arg: &ArgVal) -> String {
+    match arg {
+        ArgVal::Bool(b)  => if *b { "TRUE".to_string() } else { "FALSE".to_string() },
+        ArgVal::Int(n)   => n.to_string(),
+        ArgVal::Flag(n)  => format!("{:#x}", n),
+        ArgVal::Float(f) => format!("{:?}", f),
+        ArgVal::Vec(v)   => v.to_string(),
+        ArgVal::Id(id)   => id.to_string(),
+        ArgVal::Ptr(p)   => p.to_string(),
+        ArgVal::Obj(t)   => t.to_string(),
+        ArgVal::Param(p) => p.to_string(),
+    }
+}
+
+/// Write a unified-style diff of two lists, with `context` unchanged commands around each hunk
+pub fn write<W: Write>(mut w: W, old: &List, new: &List, context: usize) -> io::Result<Summary> {
+    let edits = align(&old.cmds, &new.cmds);
+    let mut summary = Summary::default();
+    for edit in &edits {
+        match edit {
+            Edit::Same(..)    => summary.same += 1,
+            Edit::Changed(..) => summary.changed += 1,
+            Edit::Added(..)   => summary.added += 1,
+            Edit::Removed(..) => summary.removed += 1,
+        }
+    }
+
+    writeln!(w, "--- {} ({:#X}, {} cmds)", old.label, old.address, old.cmds.len())?;
+    writeln!(w, "+++ {} ({:#X}, {} cmds)", new.label, new.address, new.cmds.len())?;
+
+    // mark every edit that's close enough to a change to be printed
+    let mut shown = vec![false; edits.len()];
+    for (i, edit) in edits.iter().enumerate() {
+        if let Edit::Same(..) = edit { continue; }
+        let end = (i + context + 1).min(edits.len());
+        for s in &mut shown[i.saturating_sub(context)..end] { *s = true; }
+    }
+
+    let line = |list: &List, i: usize| format!("{:5} {:#010X}  {}", i, list.address + i as u64 * CMD_SIZE, list.cmds[i]);
+    let mut i = 0;
+    while i < edits.len() {
+        if !shown[i] { i += 1; continue; }
+        let end = shown[i..].iter().position(|s| !s).map(|n| i + n).unwrap_or(edits.len());
+        let (old_start, new_start) = start_of(&edits[i..end]);
+        writeln!(w, "@@ command {} -> command {} @@", old_start, new_start)?;
+        for edit in &edits[i..end] {
+            match *edit {
+                Edit::Same(o, _) => writeln!(w, "  {}", line(old, o))?,
+                Edit::Removed(o) => writeln!(w, "- {}", line(old, o))?,
+                Edit::Added(n)   => writeln!(w, "+ {}", line(new, n))?,
+                Edit::Changed(o, n) => {
+                    writeln!(w, "- {}", line(old, o))?;
+                    writeln!(w, "+ {}", line(new, n))?;
+                    for change in arg_changes(&old.cmds[o], &new.cmds[n]) {
+                        writeln!(w, "  {:18}  ^ {}", "", change)?;
+                    }
+                },
+            }
+        }
+        i = end;
+    }
+    Ok(summary)
+}
+
+/// First old and new command index in a hunk
+fn start_of(edits: &[Edit]) -> (String, String) {
+    let old = edits.iter().filter_map(|e| match *e {
+        Edit::Same(o, _) | Edit::Removed(o) | Edit::Changed(o, _) => Some(o),
+        Edit::Added(..) => None,
+    }).next();
+    let new = edits.iter().filter_map(|e| match *e {
+        Edit::Same(_, n) | Edit::Added(n) | Edit::Changed(_, n) => Some(n),
+        Edit::Removed(..) => None,
+    }).next();
+    let show = |i: Option<usize>| i.map(|i| i.to_string()).unwrap_or_else(|| "-".to_string());
+    (show(old), show(new))
+}
+
+/// What an object is matched on between two scenes
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum ObjKey {
+    Named(DynId),
+    /// The `nth` anonymous object of `kind` in the innermost named `group`
+    Anon { kind: Option<DObjType>, group: Option<DynId>, nth: usize },
+}
+
+/// An object that only one scene has, or that is set up differently
+#[derive(Debug, Clone)]
+pub enum ObjChange {
+    Added(String),
+    Removed(String),
+    /// The object, and what changed about it
+    Changed(String, Vec<String>),
+}
+
+/// The key of every object in a scene
+pub fn obj_keys(scene: &Scene) -> Vec<ObjKey> {
+    let mut counts = HashMap::new();
+    scene.objs.iter().enumerate().map(|(i, obj)| match obj.id {
+        Some(id) if !id.is_null() && scene.get(id) == Some(i) => ObjKey::Named(id),
+        _ => {
+            let group = scene.groups_containing(i)
+                .filter_map(|g| scene.objs[g].id)
+                .find(|id| !id.is_null());
+            let nth = counts.entry((obj.kind, group)).or_insert(0);
+            *nth += 1;
+            ObjKey::Anon { kind: obj.kind, group, nth: *nth - 1 }
+        },
+    }).collect()
+}
+
+fn describe(obj: &Obj, key: &ObjKey) -> String {
+    let kind = obj.kind.map(|k| k.to_string()).unwrap_or_else(|| "object".to_string());
+    match *key {
+        ObjKey::Named(id) => format!("{} {:#x}", kind, id.0),
+        ObjKey::Anon { group: Some(g), nth, .. } => format!("{} #{} in group {:#x}", kind, nth, g.0),
+        ObjKey::Anon { group: None, nth, .. } => format!("{} #{}", kind, nth),
+    }
+}
+
+/// Every property of an object that a list can set, in a comparable form
+fn properties(obj: &Obj) -> Vec<(&'static str, Option<String>)> {
+    let vec = |v: Option<Vector>| v.map(|v| format!("{:?}, {:?}, {:?}", v.x, v.y, v.z));
+    let int = |n: Option<u32>| n.map(|n| n.to_string());
+    let hex = |n: Option<u32>| n.map(|n| format!("{:#x}", n));
+    let id = |id: Option<DynId>| id.map(|id| format!("{:#x}", id.0));
+    let list = |items: Vec<String>| if items.is_empty() { None } else { Some(items.join(", ")) };
+    vec![
+        ("initial position", vec(obj.init_pos)),
+        ("relative position", vec(obj.rel_pos)),
+        ("world position", vec(obj.world_pos)),
+        ("normal", vec(obj.normal)),
+        ("scale", vec(obj.scale)),
+        ("rotation", vec(obj.rotation)),
+        ("friction", vec(obj.friction)),
+        ("shape offset", vec(obj.shape_offset)),
+        ("attach offset", vec(obj.att_offset)),
+        ("centre of gravity", vec(obj.cog)),
+        ("ambient", vec(obj.ambient)),
+        ("diffuse", vec(obj.diffuse)),
+        ("spring", obj.spring.map(|f| format!("{:?}", f))),
+        ("texture st", obj.st.map(|(s, t)| format!("{:?}, {:?}", s, t))),
+        ("header flag", hex(obj.header_flag)),
+        ("colour", int(obj.colour)),
+        ("type", int(obj.obj_type)),
+        ("control type", int(obj.ctrl_type)),
+        ("id number", int(obj.num_id)),
+        ("material", int(obj.material)),
+        ("vertices", list(obj.vertices.iter().map(|v| v.to_string()).collect())),
+        ("shape", id(obj.shape)),
+        ("skin shape", id(obj.skin_shape)),
+        ("node group", id(obj.node_group)),
+        ("material group", id(obj.mtl_group)),
+        ("plane group", id(obj.plane_group)),
+        ("mapped vertices", id(obj.map_vtx)),
+        ("mapped materials", id(obj.map_mtl)),
+        ("weights", list(obj.weights.iter().map(|(v, w)| format!("{}: {:?}", v, w)).collect())),
+        ("float params", list(obj.float_params.iter().map(|(p, f)| format!("{}: {:?}", p, f)).collect())),
+    ]
+}
+
+/// The objects that `idx` is attached or linked to, and the groups it's a member of
+fn relations(scene: &Scene, keys: &[ObjKey], idx: usize) -> (Option<String>, BTreeSet<String>, BTreeSet<String>) {
+    let name = |i: usize| describe(&scene.objs[i], &keys[i]);
+    let obj = &scene.objs[idx];
+    let attached = obj.attached_to.map(|(t, flag)| format!("{} (flag {:#x})", name(t), flag));
+    let links = obj.links.iter().map(|&l| name(l)).collect();
+    let groups = scene.groups_containing(idx).map(name).collect();
+    (attached, links, groups)
+}
+
+/// Compare the objects that two lists make, ignoring the order they were made in
+pub fn scenes(old: &Scene, new: &Scene) -> Vec<ObjChange> {
+    let (old_keys, new_keys) = (obj_keys(old), obj_keys(new));
+    let new_index: HashMap<ObjKey, usize> = new_keys.iter().enumerate().map(|(i, k)| (*k, i)).collect();
+    let mut matched = vec![false; new.objs.len()];
+    let mut changes = Vec::new();
+
+    for (o, key) in old_keys.iter().enumerate() {
+        let old_obj = &old.objs[o];
+        let n = match new_index.get(key) {
+            Some(&n) => n,
+            None => {
+                changes.push(ObjChange::Removed(describe(old_obj, key)));
+                continue;
+            },
+        };
+        matched[n] = true;
+        let new_obj = &new.objs[n];
+        let mut diffs = Vec::new();
+        if old_obj.kind != new_obj.kind {
+            let kind = |o: &Obj| o.kind.map(|k| k.to_string()).unwrap_or_else(|| "unknown".to_string());
+            diffs.push(format!("type: {} -> {}", kind(old_obj), kind(new_obj)));
+        }
+        for ((name, a), (_, b)) in properties(old_obj).into_iter().zip(properties(new_obj)) {
+            if a != b {
+                let show = |v: Option<String>| v.unwrap_or_else(|| "unset".to_string());
+                diffs.push(format!("{}: {} -> {}", name, show(a), show(b)));
+            }
+        }
+        if old_obj.flags != new_obj.flags {
+            diffs.push(format!("flags: {}", flag_change(old_obj.flags, new_obj.flags)));
+        }
+
+        let (old_att, old_links, old_groups) = relations(old, &old_keys, o);
+        let (new_att, new_links, new_groups) = relations(new, &new_keys, n);
+        if old_att != new_att {
+            let show = |v: Option<String>| v.unwrap_or_else(|| "nothing".to_string());
+            diffs.push(format!("attached to: {} -> {}", show(old_att), show(new_att)));
+        }
+        for (what, a, b) in [("linked with", old_links, new_links), ("member of", old_groups, new_groups)] {
+            for gone in a.difference(&b) { diffs.push(format!("no longer {} {}", what, gone)); }
+            for added in b.difference(&a) { diffs.push(format!("now {} {}", what, added)); }
+        }
+        if !diffs.is_empty() {
+            changes.push(ObjChange::Changed(describe(old_obj, key), diffs));
+        }
+    }
+    for (n, key) in new_keys.iter().enumerate().filter(|&(n, _)| !matched[n]) {
+        changes.push(ObjChange::Added(describe(&new.objs[n], key)));
+    }
+    changes
+}
+
+/// Write the object level differences of two scenes
+pub fn write_scenes<W: Write>(mut w: W, old: (&str, &Scene), new: (&str, &Scene)) -> io::Result<Summary> {
+    let changes = scenes(old.1, new.1);
+    writeln!(w, "--- {} ({} objects)", old.0, old.1.objs.len())?;
+    writeln!(w, "+++ {} ({} objects)", new.0, new.1.objs.len())?;
+    let mut summary = Summary::default();
+    for change in &changes {
+        match change {
+            ObjChange::Removed(name) => {
+                summary.removed += 1;
+                writeln!(w, "- {}", name)?;
+            },
+            ObjChange::Added(name) => {
+                summary.added += 1;
+                writeln!(w, "+ {}", name)?;
+            },
+            ObjChange::Changed(name, diffs) => {
+                summary.changed += 1;
+                writeln!(w, "~ {}", name)?;
+                for diff in diffs {
+                    writeln!(w, "    {}", diff)?;
+                }
+            },
+        }
+    }
+    summary.same = old.1.objs.len() - summary.changed - summary.removed;
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn steps(a: &str, b: &str) -> Vec<(Option<usize>, Option<usize>)> {
+        let (a, b): (Vec<char>, Vec<char>) = (a.chars().collect(), b.chars().collect());
+        lcs(&a, &b)
+    }
+
+    #[test]
+    fn lcs_of_empty_and_disjoint_lists() {
+        assert_eq!(steps("", ""), []);
+        assert_eq!(steps("", "ab"), [(None, Some(0)), (None, Some(1))]);
+        assert_eq!(steps("ab", ""), [(Some(0), None), (Some(1), None)]);
+        assert_eq!(steps("ab", "cd"), [(Some(0), None), (Some(1), None), (None, Some(0)), (None, Some(1))]);
+    }
+
+    #[test]
+    fn lcs_of_swapped_middle() {
+        // "b" and "c" can't both match, and the tie goes to the earlier split
+        assert_eq!(steps("abcd", "acbd"),
+            [(Some(0), Some(0)), (Some(1), None), (Some(2), Some(1)), (None, Some(2)), (Some(3), Some(3))]);
+    }
+
+    #[test]
+    fn hirschberg_finds_a_longest_subsequence() {
+        let (a, b): (Vec<char>, Vec<char>) = ("ABCBDAB".chars().collect(), "BDCABA".chars().collect());
+        let mut out = Vec::new();
+        hirschberg(&a, &b, 0, 0, &mut out);
+        let matched: Vec<(usize, usize)> = out.iter().filter_map(|&s| match s {
+            (Some(i), Some(j)) => Some((i, j)),
+            _ => None,
+        }).collect();
+        assert_eq!(matched.len(), 4);
+        assert!(matched.iter().all(|&(i, j)| a[i] == b[j]));
+        assert!(matched.windows(2).all(|w| w[0].0 < w[1].0 && w[0].1 < w[1].1));
+        // every index of both sides shows up once, in order
+        let olds: Vec<usize> = out.iter().filter_map(|s| s.0).collect();
+        let news: Vec<usize> = out.iter().filter_map(|s| s.1).collect();
+        assert_eq!(olds, (0..a.len()).collect::<Vec<_>>());
+        assert_eq!(news, (0..b.len()).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn lcs_row_of_prefixes_and_suffixes() {
+        let (a, b): (Vec<char>, Vec<char>) = ("ab".chars().collect(), "xab".chars().collect());
+        assert_eq!(lcs_row(&a, &b, false), [0, 0, 1, 2]);
+        assert_eq!(lcs_row(&a, &b, true), [0, 1, 2, 2]);
+    }
+
+    #[test]
+    fn align_pairs_changes_by_kind() {
+        use dynlist::DynCmd::*;
+        let old = [Start, SetId(1), Stop];
+        let new = [Start, SetId(2), UseIntId(true), Stop];
+        assert_eq!(align(&old, &new), [Edit::Same(0, 0), Edit::Changed(1, 1), Edit::Added(2), Edit::Same(2, 3)]);
+        assert_eq!(align(&[], &[Start]), [Edit::Added(0)]);
+        assert_eq!(align(&[SetId(1)], &[Start]), [Edit::Removed(0), Edit::Added(0)]);
+        assert_eq!(align(&[], &[]), []);
+    }
+}
@@ -1,59 +1,464 @@
 use c89;
-use dynlist::{DynListIter};
-use std::io::{Write, Read};
-use failure::{Error, ResultExt};
-use std::collections::HashMap;
-
-/// Write out summary info for a dynlist
-pub fn info<W, R>(mut wtr: W, dynlist: DynListIter<R>, offset: u64) -> Result<(), Error> 
-    where W: Write, R: Read
+use json;
+use yaml;
+use dynlist::{ArgVal, DynListItem, DynCmd, DObjType, CMD_SIZE};
+use error::Error;
+use scene::Interp;
+use std::io::{self, Write};
+use serde_json;
+use std::collections::{BTreeMap, BTreeSet};
+use std::str::FromStr;
+
+/// Text formats that a dynlist can be dumped as
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Format {
+    Gas,
+    C,
+    Json,
+    Yaml,
+    /// An assembler style listing of each command's words and decoded arguments
+    Listing,
+}
+
+impl Format {
+    /// File extension for a list written in this format
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::Gas  => "s",
+            Format::C    => "c",
+            Format::Json => "json",
+            Format::Yaml => "yaml",
+            Format::Listing => "lst",
+        }
+    }
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "gas" | "asm" => Ok(Format::Gas),
+            "c"           => Ok(Format::C),
+            "json"        => Ok(Format::Json),
+            "yaml" | "yml" => Ok(Format::Yaml),
+            "listing" | "lst" => Ok(Format::Listing),
+            _ => Err(format!("unknown format \"{}\" (expected gas, c, json, yaml, or listing)", s)),
+        }
+    }
+}
+
+/// Settings shared by all of the emitters
+#[derive(Debug, Clone, Default)]
+pub struct Config {
+    /// Address of the first command of the list
+    pub address: u64,
+    /// Label for the list, instead of one made from its address
+    pub label: Option<String>,
+    /// Address of the first command in memory, if it's known
+    pub vram: Option<u64>,
+    /// Comments to add to each command
+    pub notes: Notes,
+    /// How the gas and C commands are laid out
+    pub style: Style,
+}
+
+/// Layout of the commands in the gas and C formats
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum Style {
+    /// Every command on its own line, with a single tab
+    #[default]
+    Flat,
+    /// Groups and net subgroups are indented, and there's a blank line before each `MakeDynObj`
+    Pretty,
+    /// Like `Pretty`, with the setters that follow a new object indented under it
+    Grouped,
+}
+
+/// Extra information that can be added to any format as a comment on each command
+#[derive(Debug, Copy, Clone, Default)]
+pub struct Notes {
+    /// The command's six raw words
+    pub raw: bool,
+    /// The command's file offset
+    pub offsets: bool,
+    /// Which object types the command acts on
+    pub objs: bool,
+    /// What the command does
+    pub desc: bool,
+    /// The object and group that the command acts on, found by interpreting the list
+    pub annotate: bool,
+}
+
+impl Config {
+    pub fn new(address: u64) -> Self {
+        Config { address, label: None, vram: None, notes: Notes::default(), style: Style::Flat }
+    }
+    /// The name used for the list's label or array
+    pub fn label(&self) -> String {
+        self.label.clone().unwrap_or_else(|| format!("list_{:08X}", self.address))
+    }
+}
+
+/// Pull the next command out of a dynlist, and fail on any unknown commands
+fn known(cmd: Result<DynListItem, Error>, index: usize, cfg: &Config) -> Result<DynListItem, Error> {
+    let cmd = cmd?;
+    if cmd.is_unk() {
+        let offset = cfg.address + index as u64 * CMD_SIZE;
+        return Err(Error::UnknownCmd { offset, index, raw: *cmd.raw() });
+    }
+    Ok(cmd)
+}
+
+/// Tracks how deeply each command is nested, to pretty print a list
+struct Indenter {
+    style: Style,
+    depth: usize,
+    /// An object was made, and the commands after it are setting it up
+    in_obj: bool,
+    /// The last command started a group, or is the start of the list
+    opened: bool,
+}
+
+impl Indenter {
+    fn new(style: Style) -> Self {
+        Indenter { style, depth: 0, in_obj: false, opened: true }
+    }
+
+    /// Step past a command, and return the whitespace that goes before it
+    fn next(&mut self, cmd: &DynCmd) -> String {
+        use dynlist::DynCmd::*;
+        if self.style == Style::Flat {
+            return "\t".to_string();
+        }
+        let (opens, closes) = match *cmd {
+            StartGroup(..) | NetSubGrp(..) => (true, false),
+            EndGroup(..) | EndNetGrp(..) => (false, true),
+            _ => (false, false),
+        };
+        if closes {
+            self.depth = self.depth.saturating_sub(1);
+        }
+        let mut prefix = String::new();
+        if let MakeObj(..) = *cmd {
+            if !self.opened { prefix.push('\n'); }
+        }
+        let setter = self.style == Style::Grouped && self.in_obj && !opens && !closes
+            && cmd.creates().is_none() && !cmd.info().objs.is_empty();
+        prefix.push_str(&"\t".repeat(1 + self.depth + setter as usize));
+
+        // UseObj picks an existing object to keep setting up
+        let picks = cmd.creates().is_some() && !opens || matches!(*cmd, UseObj(..));
+        self.in_obj = setter || picks;
+        self.opened = opens;
+        if opens {
+            self.depth += 1;
+        }
+        prefix
+    }
+}
+
+/// What a command acts on, from the interpreter's state after the command
+#[derive(Debug, Default)]
+struct Annotation {
+    object: Option<String>,
+    group: Option<String>,
+    warning: Option<String>,
+}
+
+/// Makes the comment for each command in a list, following along with the list's objects
+/// for annotations and flag names
+struct Commenter {
+    address: u64,
+    notes: Notes,
+    interp: Interp,
+}
+
+impl Commenter {
+    fn new(address: u64, notes: Notes) -> Self {
+        Commenter { address, notes, interp: Interp::default() }
+    }
+
+    /// Type of the object that the last command left as the current one
+    fn obj(&self) -> Option<DObjType> {
+        self.interp.current().and_then(|(_, obj)| obj.kind)
+    }
+
+    /// Step past a command, and return the parts of its comment
+    fn next(&mut self, item: &DynListItem, index: usize) -> Vec<String> {
+        let mut parts = Vec::new();
+        let info = item.info();
+        let notes = self.notes;
+        if notes.offsets {
+            parts.push(format!("{:#X}", self.address + index as u64 * CMD_SIZE));
+        }
+        if notes.raw {
+            let words: Vec<String> = item.raw().iter().map(|w| format!("{:08X}", w)).collect();
+            parts.push(words.join(" "));
+        }
+        if notes.objs && !item.is_unk() && !info.objs.is_empty() {
+            let objs = if info.objs.is_all() { "all".to_string() } else { info.objs.to_string() };
+            parts.push(format!("objs: {}", objs));
+        }
+        if notes.desc && !item.is_unk() {
+            parts.push(info.desc.to_string());
+        }
+        if let Some(note) = self.step(item) {
+            parts.extend(note.object.map(|o| format!("-> {}", o)));
+            parts.extend(note.group.map(|g| format!("in group {}", g)));
+            parts.extend(note.warning.map(|w| format!("warning: {}", w)));
+        }
+        parts
+    }
+
+    /// Step the interpreter past a command, and describe the current object and group
+    /// if the list is being annotated
+    fn step(&mut self, item: &DynListItem) -> Option<Annotation> {
+        let interp = &mut self.interp;
+        interp.step(item.cmd());
+        if !self.notes.annotate {
+            return None;
+        }
+        let info = item.info();
+        let mut note = Annotation::default();
+        // commands like StopList and JumpToList don't touch any object
+        if item.is_unk() || info.objs.is_empty() && item.cmd().creates().is_none() {
+            return Some(note);
+        }
+        // the group commands act on the group with their id, not on the current object
+        let checked = !matches!(item.cmd(), DynCmd::EndGroup(..) | DynCmd::AddToGroup(..));
+        match interp.current() {
+            Some((idx, obj)) => {
+                let kind = obj.kind.map_or_else(|| "object".to_string(), |k| k.to_string());
+                note.object = Some(match obj.id {
+                    Some(id) if !id.is_null() => format!("{} {}", kind, id),
+                    _ => format!("{} #{}", kind, idx),
+                });
+                if let Some(k) = obj.kind.filter(|&k| checked && !info.acts_on(k)) {
+                    note.warning = Some(format!("{} doesn't act on {}", info.base, k));
+                }
+            },
+            None if checked && !info.objs.is_empty() => note.warning = Some("there's no current object".to_string()),
+            None => (),
+        }
+        note.group = interp.open_group().map(|g| g.to_string());
+        Some(note)
+    }
+}
+
+/// Statistics collected while walking a dynlist
+#[derive(Debug, Default)]
+struct Summary {
+    count: u64,
+    cmds: BTreeMap<u32, (&'static str, u64)>,
+    objs: BTreeMap<DObjType, u64>,
+    groups: u64,
+    depth: u64,
+    max_depth: u64,
+    ids: BTreeSet<u32>,
+    jumps: Vec<u32>,
+}
+
+impl Summary {
+    fn add(&mut self, item: &DynListItem) {
+        let cmd = item.cmd();
+        let info = item.info();
+        self.count += 1;
+        self.cmds.entry(info.id).or_insert((info.base, 0)).1 += 1;
+
+        if let Some(t) = cmd.creates() {
+            *self.objs.entry(t).or_insert(0) += 1;
+        }
+        if let Some(id) = cmd.dyn_id() {
+            if !id.is_null() { self.ids.insert(id.0); }
+        }
+        match cmd {
+            DynCmd::StartGroup(..) | DynCmd::NetSubGrp(..) => {
+                self.groups += 1;
+                self.depth += 1;
+                self.max_depth = self.max_depth.max(self.depth);
+            },
+            DynCmd::EndGroup(..) | DynCmd::EndNetGrp(..) => {
+                self.depth = self.depth.saturating_sub(1);
+            },
+            DynCmd::Jump(ptr) => self.jumps.push(ptr.0),
+            _ => (),
+        }
+    }
+}
+
+/// Write out a dynlist in any of the text formats
+pub fn write<W, I>(format: Format, wtr: W, dynlist: I, cfg: &Config) -> Result<(), Error>
+    where W: Write, I: IntoIterator<Item = Result<DynListItem, Error>>
+{
+    match format {
+        Format::Gas  => gas(wtr, dynlist, cfg),
+        Format::C    => c(wtr, dynlist, cfg),
+        Format::Json => json(wtr, dynlist, cfg),
+        Format::Yaml => yaml(wtr, dynlist, cfg),
+        Format::Listing => listing(wtr, dynlist, cfg),
+    }
+}
+
+/// Write out summary info for a dynlist, either as text or as a JSON object
+pub fn info<W, I>(mut wtr: W, dynlist: I, cfg: &Config, as_json: bool) -> Result<(), Error> 
+    where W: Write, I: IntoIterator<Item = Result<DynListItem, Error>>
 {
-    let (info, count) = dynlist.fold( 
-        (HashMap::new(), 0u64), 
-        |(mut map, c), cmd| {
-            let cmd = cmd.expect("processing list for summarization");
-            let c = c + 1;
-            *map.entry(cmd.info().base).or_insert(0) += 1;
-            (map, c)
-        }
-    );
+    let mut summary = Summary::default();
+    for (i, cmd) in dynlist.into_iter().enumerate() {
+        summary.add(&known(cmd, i, cfg)?);
+    }
+    let offset = cfg.address;
+    let end = offset + summary.count * CMD_SIZE;
+
+    if as_json {
+        let cmds: Vec<_> = summary.cmds.iter()
+            .map(|(id, (name, num))| json!({"id": id, "name": name, "count": num}))
+            .collect();
+        let objs: serde_json::Map<_, _> = summary.objs.iter()
+            .map(|(t, num)| (t.to_string(), json!(num)))
+            .collect();
+        let report = json!({
+            "offset": offset,
+            "end": end,
+            "size": end - offset,
+            "commands": summary.count,
+            "summary": cmds,
+            "objects": objs,
+            "groups": summary.groups,
+            "max_group_depth": summary.max_depth,
+            "unique_ids": summary.ids.len(),
+            "jumps": summary.jumps,
+        });
+        serde_json::to_writer_pretty(&mut wtr, &report).map_err(io::Error::from)?;
+        writeln!(wtr)?;
+        return Ok(());
+    }
+
     writeln!(wtr, "Dynlist @ {:#X}", offset)?;
-    writeln!(wtr, "Total Commands: {}", count)?;
-    writeln!(wtr, "Total Size: {:#x} bytes", count * 6 * 4)?;
+    writeln!(wtr, "Byte Range: {:#X} - {:#X}", offset, end)?;
+    writeln!(wtr, "Total Commands: {}", summary.count)?;
+    writeln!(wtr, "Total Size: {:#x} bytes", end - offset)?;
     writeln!(wtr, "\nCommand Summary:")?;
-    for (cmd, num) in info.iter() {
-        writeln!(wtr, "{} : {}", cmd, num)?;
+    for (id, (cmd, num)) in &summary.cmds {
+        writeln!(wtr, "[{:>2}] {} : {}", id, cmd, num)?;
+    }
+    writeln!(wtr, "\nObjects Created:")?;
+    for (t, num) in &summary.objs {
+        writeln!(wtr, "{} : {}", t, num)?;
+    }
+    writeln!(wtr, "\nGroups: {} (max nesting depth {})", summary.groups, summary.max_depth)?;
+    writeln!(wtr, "Unique Ids: {}", summary.ids.len())?;
+    writeln!(wtr, "Jump Targets: {}", summary.jumps.len())?;
+    for addr in &summary.jumps {
+        writeln!(wtr, "\t{:#010X}", addr)?;
     }
     Ok(())
 }
 
-/// Write out the raw rust structs for a dynlist
-pub fn raw<W, R>(mut wtr: W, dynlist: DynListIter<R>) -> Result<(), Error> 
-    where W: Write, R: Read
+/// Write out an assembler style listing of a dynlist: the offset, address, and raw words of
+/// each command, next to the decoded command with the word that each argument came from.
+/// Words that the command doesn't use are in brackets, and any of those that aren't zero are
+/// pointed out, since the decoder drops them. Unknown commands are listed instead of stopping the dump.
+pub fn listing<W, I>(mut wtr: W, dynlist: I, cfg: &Config) -> Result<(), Error>
+    where W: Write, I: IntoIterator<Item = Result<DynListItem, Error>>
 {
-    writeln!(wtr, "Starting RAW dynlist dump")?;
-    for (i, cmd) in dynlist.enumerate() {
-        let cmd = cmd.context("reading command from dynlist iterator")?;
-        writeln!(wtr, "cmd {}: {:x?}", i, &cmd)?;
-        if cmd.is_unk() { bail!("unknown dynlist command..?") }; 
+    writeln!(wtr, "# {}: words in [] are ignored by their command", cfg.label())?;
+    writeln!(wtr, "# {:<8}  {:<10}   {:<8}  {:<8}  {:<8}  {:<8}  {:<8}  {:<8}   command",
+        "offset", "vram", "cmd", "w1", "w2", "w3", "w4", "w5")?;
+    // the offset and raw words are already columns
+    let mut commenter = Commenter::new(cfg.address, Notes { raw: false, offsets: false, ..cfg.notes });
+    for (i, cmd) in dynlist.into_iter().enumerate() {
+        let cmd = cmd?;
+        let offset = i as u64 * CMD_SIZE;
+        let vram = match cfg.vram {
+            Some(vram) => format!("{:#010X}", vram + offset),
+            None => "-".repeat(10),
+        };
+        let used = used_words(&cmd);
+        let words: String = cmd.raw().iter().enumerate()
+            .map(|(w, word)| if used[w] { format!(" {:08X} ", word) } else { format!("[{:08X}]", word) })
+            .collect();
+        write!(wtr, "  {:08X}  {}  {}  ", cfg.address + offset, vram, words)?;
+
+        let mut comment = commenter.next(&cmd, i);
+        if cmd.is_unk() {
+            writeln!(wtr, "unknown command {:#x}", cmd.raw()[0])?;
+            continue;
+        }
+        write!(wtr, "{}", cmd.info().base)?;
+        for (label, arg) in labelled_args(&cmd, commenter.obj()) {
+            write!(wtr, "  {}={}", label, arg)?;
+        }
+        let dropped: Vec<String> = cmd.raw().iter().enumerate()
+            .filter(|&(w, &word)| !used[w] && word != 0)
+            .map(|(w, word)| format!("w{}={:#x}", w, word))
+            .collect();
+        if !dropped.is_empty() {
+            comment.insert(0, format!("ignored {}", dropped.join(", ")));
+        }
+        if !comment.is_empty() {
+            write!(wtr, "  # {}", comment.join(" | "))?;
+        }
+        writeln!(wtr)?;
     }
-    writeln!(wtr, "Finished RAW dynlist dump")?;
     Ok(())
 }
 
+/// Which of a command's six words are read by the game
+fn used_words(cmd: &DynListItem) -> [bool; 6] {
+    if cmd.is_unk() { return [true; 6]; }
+    let mut used = [true, false, false, false, false, false];
+    for &slot in cmd.info().kind.slots() {
+        used[slot] = true;
+    }
+    used
+}
+
+/// A command's arguments, labelled with the word they're in and what kind of value they are.
+/// `obj` is the type of the current object, for naming flags and types.
+fn labelled_args(cmd: &DynListItem, obj: Option<DObjType>) -> Vec<(String, String)> {
+    let slots = cmd.info().kind.slots();
+    let named = cmd.cmd().symbolic_arg(obj);
+    cmd.cmd().args().iter().zip(slots).enumerate().map(|(i, (arg, slot))| {
+        let value = match *arg {
+            ArgVal::Bool(b)  => (if b { "TRUE" } else { "FALSE" }).to_string(),
+            ArgVal::Int(n)   => n.to_string(),
+            ArgVal::Flag(n)  => format!("{:#x}", n),
+            ArgVal::Float(f) => format!("{:?}", f),
+            ArgVal::Vec(v)   => return ("vec".to_string(), format!("({:?}, {:?}, {:?})", v.x, v.y, v.z)),
+            ArgVal::Id(id)   => format!("{:#x}", id.0),
+            ArgVal::Ptr(p)   => format!("{:#010x}", p.0),
+            ArgVal::Obj(t)   => t.to_string(),
+            ArgVal::Param(p) => p.to_string(),
+        };
+        let value = match named {
+            Some((at, ref name)) if at == i => name.clone(),
+            _ => value,
+        };
+        (format!("w{}:{}", slot, arg.kind()), value)
+    }).collect()
+}
+
 /// Write out a C style array for a dynlist
-pub fn c<W, R>(mut wtr: W, dynlist: DynListIter<R>, address: u64) -> Result<(), Error> 
-    where W: Write, R: Read
+pub fn c<W, I>(mut wtr: W, dynlist: I, cfg: &Config) -> Result<(), Error> 
+    where W: Write, I: IntoIterator<Item = Result<DynListItem, Error>>
 {
     let mut count = 0;
     let prefix = c89::PREFIX;
     let structname = c89::STRUCT_NAME;
 
-    writeln!(wtr, "{} list_{:08X}[] = {{", structname, address)?;
-    for cmd in dynlist {
-        let cmd = cmd.context("reading command from dynlist iterator")?;
-        writeln!(wtr, "\t{}{:b},", prefix, cmd)?;
-        if cmd.is_unk() { bail!("unknown dynlist command") };
+    writeln!(wtr, "{} {}[] = {{", structname, cfg.label())?;
+    let mut commenter = Commenter::new(cfg.address, cfg.notes);
+    let mut indenter = Indenter::new(cfg.style);
+    for (i, cmd) in dynlist.into_iter().enumerate() {
+        let cmd = known(cmd, i, cfg)?;
+        let comment = commenter.next(&cmd, i);
+        write!(wtr, "{}{}{:b},", indenter.next(cmd.cmd()), prefix, cmd.cmd().for_obj(commenter.obj()))?;
+        if !comment.is_empty() {
+            write!(wtr, "  /* {} */", comment.join(" | "))?;
+        }
+        writeln!(wtr)?;
         count += 1;
     }
     writeln!(wtr, "}};")?;
@@ -62,14 +467,71 @@ pub fn c<W, R>(mut wtr: W, dynlist: DynListIter<R>, address: u64) -> Result<(),
 }
 
 /// Write out a GNU AS file of macros for a dynlist
-pub fn gas<W, R>(mut wtr: W, dynlist: DynListIter<R>, address: u64) -> Result<(), Error> 
-    where W: Write, R: Read
+pub fn gas<W, I>(mut wtr: W, dynlist: I, cfg: &Config) -> Result<(), Error> 
+    where W: Write, I: IntoIterator<Item = Result<DynListItem, Error>>
 {
-    writeln!(wtr, "list_{:08X}:", address);
-    for cmd in dynlist {
-        let cmd = cmd.context("reading command from dynlist iterator")?;
-        writeln!(wtr, "\t{}", &cmd)?;
-        if cmd.is_unk() { bail!("unknown dynlist command..?") }; 
+    writeln!(wtr, "{}:", cfg.label())?;
+    let mut commenter = Commenter::new(cfg.address, cfg.notes);
+    let mut indenter = Indenter::new(cfg.style);
+    for (i, cmd) in dynlist.into_iter().enumerate() {
+        let cmd = known(cmd, i, cfg)?;
+        let comment = commenter.next(&cmd, i);
+        write!(wtr, "{}{}", indenter.next(cmd.cmd()), cmd.cmd().for_obj(commenter.obj()))?;
+        if !comment.is_empty() {
+            write!(wtr, "  # {}", comment.join(" | "))?;
+        }
+        writeln!(wtr)?;
+    }
+    Ok(())
+}
+
+/// Write out a JSON document with every decoded command of a dynlist
+pub fn json<W, I>(mut wtr: W, dynlist: I, cfg: &Config) -> Result<(), Error> 
+    where W: Write, I: IntoIterator<Item = Result<DynListItem, Error>>
+{
+    let mut cmds = Vec::new();
+    let mut commenter = Commenter::new(cfg.address, cfg.notes);
+    for (i, cmd) in dynlist.into_iter().enumerate() {
+        let cmd = known(cmd, i, cfg)?;
+        let note = commenter.step(&cmd);
+        let mut value = json::item_to_value(&cmd, i, cfg.address + i as u64 * CMD_SIZE, commenter.obj());
+        // offsets, raw words, and object types are always in the JSON
+        if cfg.notes.desc {
+            value["desc"] = json!(cmd.info().desc);
+        }
+        if let Some(note) = note {
+            value["object"] = json!(note.object);
+            value["group"] = json!(note.group);
+            value["warning"] = json!(note.warning);
+        }
+        cmds.push(value);
+    }
+    let doc = json!({
+        "address": cfg.address,
+        "label": cfg.label(),
+        "commands": cmds,
+    });
+    serde_json::to_writer_pretty(&mut wtr, &doc).map_err(io::Error::from)?;
+    writeln!(wtr)?;
+    Ok(())
+}
+
+/// Write out a hand-editable YAML list
+pub fn yaml<W, I>(wtr: W, dynlist: I, cfg: &Config) -> Result<(), Error> 
+    where W: Write, I: IntoIterator<Item = Result<DynListItem, Error>>
+{
+    let mut items = Vec::new();
+    let mut comments = Vec::new();
+    let mut commenter = Commenter::new(cfg.address, cfg.notes);
+    for (i, cmd) in dynlist.into_iter().enumerate() {
+        let cmd = known(cmd, i, cfg)?;
+        comments.push(commenter.next(&cmd, i));
+        items.push(cmd);
+    }
+    let mut doc = yaml::Document::from_items(items, &cfg.label());
+    for (i, comment) in comments.into_iter().enumerate() {
+        if !comment.is_empty() { doc.comment(i, &comment.join(" | ")); }
     }
+    doc.write(wtr)?;
     Ok(())
 }
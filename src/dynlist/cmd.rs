@@ -2,12 +2,14 @@ use std::fmt;
 use dynlist::dobj_types::DObjType;
 use dynlist::param_ptr::PtrParam;
 use dynlist::objs;
+use dynlist::flags;
+use dynlist::values::{self, Colour, FloatParam};
 
 /// This is used by the game as a pointer, so be able to indicate it
-#[derive(Debug)]
-pub struct Ptr(u32);
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct Ptr(pub u32);
 impl Ptr {
-    const NULL: Ptr = Ptr(0);
+    pub const NULL: Ptr = Ptr(0);
 }
 impl fmt::Display for Ptr {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -17,10 +19,14 @@ impl fmt::Display for Ptr {
 
 // Maybe want to add something to check if this is char * vs an int?
 /// The weird maybe-int, maybe-char* id type. 
-#[derive(Debug)]
-pub struct DynId(u32);
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct DynId(pub u32);
 impl DynId {
-    const NULL: DynId = DynId(0);
+    pub const NULL: DynId = DynId(0);
+
+    pub fn is_null(&self) -> bool {
+        self.0 == 0
+    }
 }
 impl fmt::Display for DynId {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -28,17 +34,17 @@ impl fmt::Display for DynId {
     }
 }
 
-#[derive(Debug, Default, Copy, Clone)]
-pub struct Vector{ x: f32, y: f32, z: f32 }
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct Vector{ pub x: f32, pub y: f32, pub z: f32 }
 impl Vector {
-    const ZERO: Vector = Vector{x: 0.0, y: 0.0, z: 0.0};
+    pub const ZERO: Vector = Vector{x: 0.0, y: 0.0, z: 0.0};
 }
 impl fmt::Display for Vector {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "Vec<{},{},{}>", self.x, self.y, self.z)
     }
 }
-impl<'a> From<&'a [u32]> for Vector {
+impl From<&[u32]> for Vector {
     fn from(arr: &[u32]) -> Self {
         assert!(arr.len() >= 3);
         let x = f32::from_bits(arr[0]);
@@ -64,6 +70,88 @@ pub enum DynArg {
     ValPtr,
 }
 
+impl DynArg {
+    /// The index of the raw command word that each macro argument is stored in.
+    /// Words 1 and 2 are integers, while words 3 through 5 are floats.
+    pub fn slots(&self) -> &'static [usize] {
+        use self::DynArg::*;
+        match self {
+            Void            => &[],
+            First           => &[1],
+            Second          => &[2],
+            Both            => &[1, 2],
+            SwapBoth        => &[2, 1],
+            VecXYZ | VecPtr => &[3, 4, 5],
+            VecX            => &[3],
+            VecXY           => &[3, 4],
+            SecVecX         => &[2, 3],
+            ValPtr          => &[1, 4, 2, 3],
+        }
+    }
+    /// The names of the macro arguments, matching `slots`
+    pub fn params(&self) -> &'static [&'static str] {
+        use self::DynArg::*;
+        match self {
+            Void            => &[],
+            First           => &["w1"],
+            Second          => &["w2"],
+            Both            => &["w1", "w2"],
+            SwapBoth        => &["w2", "w1"],
+            VecXYZ | VecPtr => &["x", "y", "z"],
+            VecX            => &["x"],
+            VecXY           => &["x", "y"],
+            SecVecX         => &["w2", "x"],
+            ValPtr          => &["id", "flags", "type", "offset"],
+        }
+    }
+}
+
+/// A decoded command argument, tagged with how it is used by the game
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ArgVal {
+    Bool(bool),
+    Int(u32),
+    Flag(u32),
+    Float(f32),
+    Vec(Vector),
+    Id(DynId),
+    Ptr(Ptr),
+    Obj(DObjType),
+    Param(PtrParam),
+}
+
+impl ArgVal {
+    /// Short name of the type of argument
+    pub fn kind(&self) -> &'static str {
+        use self::ArgVal::*;
+        match self {
+            Bool(..)  => "bool",
+            Int(..)   => "int",
+            Flag(..)  => "flag",
+            Float(..) => "float",
+            Vec(..)   => "vector",
+            Id(..)    => "id",
+            Ptr(..)   => "ptr",
+            Obj(..)   => "obj",
+            Param(..) => "param",
+        }
+    }
+    /// The raw command words that this argument is stored in
+    pub fn words(&self) -> Vec<u32> {
+        use self::ArgVal::*;
+        match *self {
+            Bool(b)   => vec![b as u32],
+            Int(n) | Flag(n) => vec![n],
+            Float(f)  => vec![f.to_bits()],
+            Vec(v)    => vec![v.x.to_bits(), v.y.to_bits(), v.z.to_bits()],
+            Id(id)    => vec![id.0],
+            Ptr(p)    => vec![p.0],
+            Obj(t)    => vec![t as u32],
+            Param(p)  => vec![p as u32],
+        }
+    }
+}
+
 /// Printing info for all commands
 #[derive(Debug)]
 pub struct CmdInfo {
@@ -74,8 +162,17 @@ pub struct CmdInfo {
     pub id: u32,
 }
 
+impl CmdInfo {
+    /// Can this command act on an object of type `kind`. Commands that don't act on
+    /// objects, and types that the game has no flag for, are never flagged as a mismatch.
+    pub fn acts_on(&self, kind: DObjType) -> bool {
+        let flag = objs::ObjFlag::of(kind);
+        self.objs.is_empty() || flag.is_empty() || self.objs.intersects(flag)
+    }
+}
+
 /// All DynList commands as determined from function [Name; OFFSET] in SM64 J (GAME ID)
-#[derive(Debug)]
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub enum DynCmd {
     Start,
     Stop,
@@ -91,7 +188,7 @@ pub enum DynCmd {
     ClearFlag(u32),
     SetFriction(Vector),
     SetSpring(f32),
-    SetColourNum(u32),  // TODO: ennumerate?
+    SetColourNum(u32),
     Jump(Ptr),
     MakeObj(DObjType, DynId),
     StartGroup(DynId),
@@ -136,7 +233,9 @@ pub enum DynCmd {
 }
 
 impl DynCmd {
-    pub fn from_struct(cmd: &[u32; 6]) -> Self {
+    /// Decode raw words. Panics on an unknown `DObjType` or `PtrParam`, so the words need to
+    /// pass `check_raw` first.
+    pub(crate) fn from_struct(cmd: &[u32; 6]) -> Self {
         use self::DynCmd::*;
         match cmd[0] {
             0xD1D4 => Start,
@@ -196,7 +295,7 @@ impl DynCmd {
                     DynId(cmd[1]), 
                     f32::from_bits(cmd[4]),
                     cmd[2],
-                    f32::from_bits(cmd[1]),
+                    f32::from_bits(cmd[3]),
                 ),
             // missing 51
             52 => UseTx(Ptr(cmd[2])),
@@ -204,9 +303,13 @@ impl DynCmd {
             54 => NetFromId(DynId(cmd[1])),
             55 => NetFromPtr(Ptr(cmd[1])),
             // missing 56 and 57
-            u @ _ => Unk(u),
+            u => Unk(u),
         }
     }
+    /// Find the info for a command by its macro name
+    pub fn lookup(name: &str) -> Option<CmdInfo> {
+        DynCmd::variants().find(|info| info.base == name)
+    }
     /// Create an iterator over the real/necessary variants of the DynCmd enum
     pub fn variants() -> impl Iterator<Item=CmdInfo> {
         use self::DynCmd::*;
@@ -230,9 +333,84 @@ impl DynCmd {
             AttNetJoint(0, DynId::NULL), EndNetGrp(DynId::NULL), MakeVtx(Vector::ZERO), 
             MakeValPtr(DynId::NULL, 0.0, 0, 0.0), UseTx(Ptr::NULL), SetTxST(0.0, 0.0), 
             NetFromId(DynId::NULL), NetFromPtr(Ptr::NULL),
-        ].into_iter()
+        ].iter()
         .map(|c| c.info())
     }
+    /// Encode a command back into its six raw words
+    pub fn to_struct(self) -> [u32; 6] {
+        let mut raw = [0; 6];
+        if let DynCmd::Unk(n) = self {
+            raw[0] = n;
+            return raw;
+        }
+        let info = self.info();
+        raw[0] = info.id;
+        let words = self.args().iter().flat_map(ArgVal::words).collect::<Vec<_>>();
+        for (&slot, word) in info.kind.slots().iter().zip(words) {
+            raw[slot] = word;
+        }
+        raw
+    }
+    /// The decoded arguments of a command, in the same order as its macro
+    pub fn args(&self) -> Vec<ArgVal> {
+        use self::DynCmd::*;
+        use self::ArgVal as A;
+        match *self {
+            Start | Stop | Unk(..)  => vec![],
+            UseIntId(b)             => vec![A::Bool(b)],
+            SetInitPos(v) | SetRelPos(v) | SetWorldPos(v) | SetNormal(v)
+            | SetScale(v) | SetRotation(v) | SetFriction(v) | SetShpOff(v)
+            | SetCoG(v) | SetAmbient(v) | SetDiffuse(v) | SetAttOff(v)
+            | MakeVtx(v)            => vec![A::Vec(v)],
+            SetHeaderFlag(flag) | SetFlag(flag) | ClearFlag(flag)
+                                    => vec![A::Flag(flag)],
+            SetSpring(f)            => vec![A::Float(f)],
+            SetColourNum(n) | SetType(n) | SetCtrlType(n) | SetId(n)
+                                    => vec![A::Int(n)],
+            MakeObj(t, id)          => vec![A::Obj(t), A::Id(id)],
+            StartGroup(id) | EndGroup(id) | AddToGroup(id) | SetMtlGroup(id)
+            | SetNodeGroup(id) | SetSkinShape(id) | SetPlaneGroup(id) | SetShpPtr(id)
+            | LinkWith(id) | UseObj(id) | MapMtls(id) | MapVtx(id) | Attach(id)
+            | EndNetGrp(id) | NetFromId(id)
+                                    => vec![A::Id(id)],
+            Jump(p) | SetShpPtrPtr(p) | LinkWithPtr(p) | CpyStrId(p) | UseTx(p)
+            | NetFromPtr(p)         => vec![A::Ptr(p)],
+            SetSkinWgt(n, f) | ParamF(n, f)
+                                    => vec![A::Int(n), A::Float(f)],
+            SetMtl(p, n)            => vec![A::Ptr(p), A::Int(n)],
+            AttachTo(flag, id)      => vec![A::Flag(flag), A::Id(id)],
+            ParamPtr(param, p)      => vec![A::Param(param), A::Ptr(p)],
+            NetSubGrp(n, id) | AttNetJoint(n, id)
+                                    => vec![A::Int(n), A::Id(id)],
+            MakeValPtr(id, flags, kind, off)
+                                    => vec![A::Id(id), A::Float(flags), A::Int(kind), A::Float(off)],
+            SetTxST(s, t)           => vec![A::Float(s), A::Float(t)],
+        }
+    }
+    /// The dynobj id that this command uses as an argument, if any
+    pub fn dyn_id(&self) -> Option<&DynId> {
+        use self::DynCmd::*;
+        match self {
+            MakeObj(_, id) | StartGroup(id) | EndGroup(id) | AddToGroup(id)
+            | SetMtlGroup(id) | SetNodeGroup(id) | SetSkinShape(id) | SetPlaneGroup(id)
+            | SetShpPtr(id) | LinkWith(id) | UseObj(id) | MapMtls(id) | MapVtx(id)
+            | Attach(id) | AttachTo(_, id) | NetSubGrp(_, id) | AttNetJoint(_, id)
+            | EndNetGrp(id) | MakeValPtr(id, ..) | NetFromId(id) => Some(id),
+            _ => None,
+        }
+    }
+    /// The type of dynobj that is created when this command is processed, if any
+    pub fn creates(&self) -> Option<DObjType> {
+        use self::DynCmd::*;
+        match self {
+            MakeObj(t, _) => Some(*t),
+            StartGroup(..) => Some(DObjType::D_GROUP),
+            NetSubGrp(..) | NetFromId(..) | NetFromPtr(..) => Some(DObjType::D_NET),
+            AttNetJoint(..) => Some(DObjType::D_JOINT),
+            MakeVtx(..) => Some(DObjType::D_VERTEX),
+            _ => None,
+        }
+    }
     /// Basic info for a command
     pub fn info(&self) -> CmdInfo {
         use self::DynCmd::*;
@@ -549,7 +727,7 @@ impl DynCmd {
             }, 
             ParamF(..) => CmdInfo {
                 base: "SetParamF",
-                desc: "Set float paramter PARAM to VALUE. TODO: Ennumerate parameters",
+                desc: "Set float parameter PARAM to VALUE",
                 kind: SecVecX,
                 objs: O::SHAPES | O::GADGETS | O::VERTICES,
                 id: 44,
@@ -637,12 +815,56 @@ impl DynCmd {
 
 impl fmt::Display for DynCmd {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        fmt_cmd(f, self, MacroT::Gas)
+        fmt_cmd(f, self, MacroT::Gas, None)
     }
 }
 impl fmt::Binary for DynCmd {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        fmt_cmd(f, self, MacroT::C)
+        fmt_cmd(f, self, MacroT::C, None)
+    }
+}
+
+impl DynCmd {
+    /// Format the command knowing the type of the object that it acts on, so that its
+    /// flags and enumerated values are written out by name
+    pub fn for_obj(&self, obj: Option<DObjType>) -> ObjCmd<'_> {
+        ObjCmd { cmd: self, obj }
+    }
+    /// The argument (as an index into `args`) that can be written by name, given the type of
+    /// the current object, and that name. Flags are or'd together, like `A | B`. Values that
+    /// don't have a name are left as numbers.
+    pub fn symbolic_arg(&self, obj: Option<DObjType>) -> Option<(usize, String)> {
+        use self::DynCmd::*;
+        let value = |v: u32, names: &[(&str, u32)]| names.iter()
+            .find(|&&(_, n)| n == v)
+            .map(|&(name, _)| (0, name.to_string()));
+        match *self {
+            SetHeaderFlag(flag) => flags::cmd_flags(self)
+                .filter(|names| names.iter().any(|&(_, bits)| flag & bits == bits))
+                .map(|names| (0, flags::flag_expr(flag, names))),
+            SetType(t) => obj.and_then(values::object_types).and_then(|names| value(t, names)),
+            SetColourNum(c) => value(c, Colour::NAMES),
+            ParamF(param, _) => value(param, FloatParam::NAMES),
+            _ => None,
+        }
+    }
+}
+
+/// A command and the type of the current object, which formats like the command
+/// but with symbolic flags and values
+#[derive(Debug, Copy, Clone)]
+pub struct ObjCmd<'a> {
+    cmd: &'a DynCmd,
+    obj: Option<DObjType>,
+}
+impl<'a> fmt::Display for ObjCmd<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt_cmd(f, self.cmd, MacroT::Gas, Some(self.obj))
+    }
+}
+impl<'a> fmt::Binary for ObjCmd<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt_cmd(f, self.cmd, MacroT::C, Some(self.obj))
     }
 }
 
@@ -652,10 +874,18 @@ enum MacroT {
     C,
 }
 
-fn fmt_cmd(f: &mut fmt::Formatter, cmd: &DynCmd, mt: MacroT) -> fmt::Result {
+/// `obj` is the type of the current object if the flags and values should be named,
+/// which can itself be unknown
+fn fmt_cmd(f: &mut fmt::Formatter, cmd: &DynCmd, mt: MacroT, obj: Option<Option<DObjType>>) -> fmt::Result {
     use self::DynCmd::*;
     let info = cmd.info();
     let n = info.base;
+    if let Some((_, name)) = obj.and_then(|kind| cmd.symbolic_arg(kind)) {
+        return match *cmd {
+            ParamF(_, fl) => int_and_hex(f, mt, n, name, fl),
+            _ => one_param(f, mt, n, name),
+        };
+    }
     match cmd {
         Start                       => void_macro(f, mt, n),
         Stop                        => void_macro(f, mt, n),
@@ -783,7 +1013,7 @@ fn val_ptr(f: &mut fmt::Formatter, t: MacroT, name: &str, id: &DynId, flags: f32
     -> fmt::Result
 {
     match t {
-        MacroT::Gas => write!(f, "{} {:?} {:?} {} {:?}", name, id, flags, kind, offset),
-        MacroT::C   => write!(f, "{}({:?} {:?} {} {:?})", name, id, flags, kind, offset),
+        MacroT::Gas => write!(f, "{} {}, {:?}, {}, {:?}", name, id, flags, kind, offset),
+        MacroT::C   => write!(f, "{}({}, {:?}, {}, {:?})", name, id, flags, kind, offset),
     }
 }
@@ -1,6 +1,6 @@
 use std::{fmt};
 
-#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
 #[allow(non_camel_case_types)]
 pub enum DObjType {
     D_CAR_DYNAMICS  = 0,
@@ -60,7 +60,7 @@ impl From<u32> for DObjType {
             16 => DObjType::D_PARTICLE,
             17 => DObjType::D_LIGHT,
             18 => DObjType::D_GROUP,
-            u @ _ => panic!("Unknown DynObj Type {}", u), 
+            u => panic!("Unknown DynObj Type {}", u), 
         }
     }
 }
\ No newline at end of file
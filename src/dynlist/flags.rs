@@ -0,0 +1,38 @@
+use dynlist::DynCmd;
+use dynlist::objs::HeaderFlag;
+
+/// Every table of flags, with a description for the generated constants. The bits of
+/// SetFlag and ClearFlag depend on the object, and none of them have known names yet.
+pub const FLAG_TABLES: [(&str, &[(&str, u32)]); 1] = [
+    ("Object header flags for the SetHeaderFlag command", HeaderFlag::NAMES),
+];
+
+/// The flags that a command's flag argument uses, if their names are known
+pub fn cmd_flags(cmd: &DynCmd) -> Option<&'static [(&'static str, u32)]> {
+    match *cmd {
+        DynCmd::SetHeaderFlag(..) => Some(HeaderFlag::NAMES),
+        _ => None,
+    }
+}
+
+/// Write `value` as the flags in `names` or'd together, with any bits that don't have a name
+/// left as a hex number at the end
+pub fn flag_expr(value: u32, names: &[(&str, u32)]) -> String {
+    flag_names(value, names).join(" | ")
+}
+
+/// The names of the flags in `value`, followed by any bits that don't have a name as a hex number
+pub fn flag_names(value: u32, names: &[(&str, u32)]) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut rest = value;
+    for &(name, bits) in names {
+        if value & bits == bits {
+            parts.push(name.to_string());
+            rest &= !bits;
+        }
+    }
+    if rest != 0 || parts.is_empty() {
+        parts.push(format!("{:#x}", rest));
+    }
+    parts
+}
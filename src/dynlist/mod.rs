@@ -1,17 +1,28 @@
 use byteorder::{ByteOrder, BE};
+use error::Error;
 use std::io::{self, Read, Seek, SeekFrom};
 use std::fmt;
 
 mod cmd;
 mod dobj_types;
+mod flags;
+mod values;
 mod param_ptr;
 mod objs;
-pub use self::cmd::{DynCmd, CmdInfo, DynArg};
+mod probe;
+pub use self::cmd::{DynCmd, CmdInfo, DynArg, ArgVal, DynId, Ptr, Vector, ObjCmd};
 pub use self::dobj_types::DObjType;
-pub use self::objs::ObjFlag;
+pub use self::objs::{ObjFlag, HeaderFlag};
 pub use self::param_ptr::PtrParam;
+pub use self::flags::FLAG_TABLES;
+pub(crate) use self::flags::{cmd_flags, flag_names};
+pub use self::values::{Colour, NetType, FloatParam, VALUE_TABLES};
+pub use self::probe::{Confidence, probe, PROBE_CMDS};
 
-#[derive(Debug)]
+/// Size in bytes of a single dynlist command
+pub const CMD_SIZE: u64 = 24;
+
+#[derive(Debug, Clone)]
 pub struct DynListItem  {
     cmd: DynCmd,
     raw: [u32; 6],
@@ -30,45 +41,147 @@ impl fmt::Binary for DynListItem {
 }
 
 impl DynListItem  {
-    fn from_bytes(buf: &[u8; 24]) -> Self {
-        let mut raw = [0; 6];
-        BE::read_u32_into(buf, &mut raw);
+    /// Decode raw words whose enumerated arguments have already been checked
+    pub(crate) fn from_raw(raw: [u32; 6]) -> Self {
         let cmd = DynCmd::from_struct(&raw);
         DynListItem {raw, cmd}
     }
-    fn is_end(&self) -> bool {
-        match self.cmd {
-            DynCmd::Stop => true,
-            _ => false,
+    /// Decode the raw words of the command at `offset` and `index` in a list. Unknown
+    /// commands decode as `DynCmd::Unk`, but an unknown enumerated argument is an error.
+    pub fn decode(raw: [u32; 6], offset: u64, index: usize) -> Result<Self, Error> {
+        match invalid_arg(&raw) {
+            Some((word, msg)) => Err(Error::InvalidArg { offset, index, raw, word, msg }),
+            None => Ok(DynListItem::from_raw(raw)),
         }
     }
+    pub fn from_cmd(cmd: DynCmd) -> Self {
+        DynListItem {raw: cmd.to_struct(), cmd}
+    }
+    pub fn to_bytes(&self) -> [u8; 24] {
+        let mut buf = [0; 24];
+        BE::write_u32_into(&self.raw, &mut buf);
+        buf
+    }
+    fn is_end(&self) -> bool {
+        matches!(self.cmd, DynCmd::Stop)
+    }
     pub fn is_unk(&self) -> bool {
-        match self.cmd {
-            DynCmd::Unk(_) => true,
-            _ => false,
-        }
+        matches!(self.cmd, DynCmd::Unk(_))
     }
     pub fn info(&self) -> CmdInfo {
         self.cmd.info()
     }
+    pub fn cmd(&self) -> &DynCmd {
+        &self.cmd
+    }
+    pub fn raw(&self) -> &[u32; 6] {
+        &self.raw
+    }
+}
+
+/// Look up the value of a named constant from the generated macro files
+pub(crate) fn lookup_constant(name: &str) -> Option<u32> {
+    match name {
+        "TRUE" => Some(1),
+        "FALSE" | "NULL" => Some(0),
+        _ => DObjType::iter()
+            .find(|(t, _)| t.to_string() == name)
+            .map(|(_, v)| v)
+            .or_else(|| PtrParam::iter()
+                .find(|(p, _)| p.to_string() == name)
+                .map(|&(_, v)| v)
+            )
+            .or_else(|| FLAG_TABLES.iter().chain(VALUE_TABLES.iter())
+                .flat_map(|(_, names)| names.iter())
+                .find(|(flag, _)| *flag == name)
+                .map(|&(_, v)| v)
+            ),
+    }
+}
+
+/// Parse a decimal, hex (`0x`), or named constant integer argument
+pub(crate) fn parse_int(s: &str) -> Option<u32> {
+    if let Some(hex) = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        u32::from_str_radix(hex, 16).ok()
+    } else if let Some(neg) = s.strip_prefix('-') {
+        neg.parse::<i64>().ok().filter(|&n| n <= 1 << 31).map(|n| (-n) as u32)
+    } else {
+        s.parse().ok().or_else(|| lookup_constant(s))
+    }
+}
+
+/// Parse an integer, or several of them or'd together with `|` like a flag expression
+pub(crate) fn parse_int_expr(s: &str) -> Option<u32> {
+    s.split('|').try_fold(0, |acc, part| parse_int(part.trim()).map(|n| acc | n))
+}
+
+/// Check that any enumerated arguments of a raw command are values the game knows about
+pub(crate) fn check_raw(raw: &[u32; 6]) -> Result<(), String> {
+    match invalid_arg(raw) {
+        Some((_, msg)) => Err(msg),
+        None => Ok(()),
+    }
+}
+
+/// The index of the raw word holding an unknown enumerated value, and what's wrong with it
+fn invalid_arg(raw: &[u32; 6]) -> Option<(usize, String)> {
+    match raw[0] {
+        15 if raw[2] >= DObjType::iter().count() as u32 => 
+            Some((2, format!("unknown dynobj type {}", raw[2]))),
+        45 if !PtrParam::iter().any(|&(_, v)| v == raw[2]) =>
+            Some((2, format!("unknown SetParamPtr parameter {}", raw[2]))),
+        _ => None,
+    }
+}
+
+/// Where to give up on a list that doesn't seem to end
+#[derive(Debug, Copy, Clone, Default)]
+pub struct Limits {
+    /// Most commands to read, including the `StopList`
+    pub max_cmds: Option<usize>,
+    /// Most bytes to read from the start of the list
+    pub max_bytes: Option<u64>,
+    /// Fail on an unknown command, rather than passing it along
+    pub stop_at_unknown: bool,
+    /// Fail on a `StartList` after the first command, as that is probably the next list
+    pub stop_at_start: bool,
 }
 
 pub struct DynListIter<R> {
     buf: [u8; 24],
     end_found: bool,
     rdr: R,
+    /// File offset of the first command
+    start: u64,
+    /// File offset of the next command
+    offset: u64,
+    /// Index of the next command
+    index: usize,
+    limits: Limits,
 }
 
 impl<R: Read + Seek> DynListIter<R> {
-    pub fn from_reader(mut rdr: R, offset: u64) -> Result<Self, io::Error> {
+    pub fn from_reader(mut rdr: R, offset: u64) -> Result<Self, Error> {
+        let size = rdr.seek(SeekFrom::End(0))?;
+        if offset >= size {
+            return Err(Error::OutOfRange { offset, size });
+        }
         rdr.seek(SeekFrom::Start(offset))?;
 
         Ok(DynListIter {
             buf: [0; 24],
             end_found: false,
-            rdr
+            rdr,
+            start: offset,
+            offset,
+            index: 0,
+            limits: Limits::default(),
         })
     }
+    pub fn limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
     /*
     pub fn into_reader(self) -> R {
         self.rdr
@@ -76,17 +189,66 @@ impl<R: Read + Seek> DynListIter<R> {
     */
 }
 
+impl<R: Read> DynListIter<R> {
+    /// Fill the command buffer, returning how many bytes could be read
+    fn fill(&mut self) -> io::Result<usize> {
+        let mut read = 0;
+        while read < self.buf.len() {
+            match self.rdr.read(&mut self.buf[read..]) {
+                Ok(0) => break,
+                Ok(n) => read += n,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => (),
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(read)
+    }
+}
+
 impl<R: Read> Iterator for DynListIter<R> {
-    type Item = Result<DynListItem, io::Error>;
+    type Item = Result<DynListItem, Error>;
 
     fn next(&mut self) -> Option<Self::Item> {
         if self.end_found { return None; }
-        if let Err(e) = self.rdr.read_exact(&mut self.buf) {
-            return Some(Err(e.into()));
+        let (offset, index) = (self.offset, self.index);
+        let limits = self.limits;
+        let runaway = limits.max_cmds.filter(|&max| index >= max)
+            .map(|max| format!("the limit of {} commands", max))
+            .or_else(|| limits.max_bytes.filter(|&max| offset + CMD_SIZE - self.start > max)
+                .map(|max| format!("the limit of {:#x} bytes", max)));
+        if let Some(limit) = runaway {
+            self.end_found = true;
+            return Some(Err(Error::Runaway { offset, index, limit }));
         }
 
-        let cmd = DynListItem::from_bytes(&self.buf);
+        let err = match self.fill() {
+            Ok(read) if read == self.buf.len() => None,
+            Ok(0) => Some(Error::MissingStop { offset, index }),
+            Ok(read) => Some(Error::UnexpectedEof { offset, index, read }),
+            Err(e) => Some(Error::Io(e)),
+        };
+        if let Some(e) = err {
+            self.end_found = true;
+            return Some(Err(e));
+        }
+        self.offset += CMD_SIZE;
+        self.index += 1;
+
+        let mut raw = [0; 6];
+        BE::read_u32_into(&self.buf, &mut raw);
+        let cmd = match DynListItem::decode(raw, offset, index) {
+            Ok(cmd) => cmd,
+            Err(e) => return Some(Err(e)),
+        };
         self.end_found = cmd.is_end();
+        if limits.stop_at_unknown && cmd.is_unk() {
+            self.end_found = true;
+            return Some(Err(Error::UnknownCmd { offset, index, raw }));
+        }
+        if limits.stop_at_start && index > 0 && matches!(cmd.cmd(), DynCmd::Start) {
+            self.end_found = true;
+            return Some(Err(Error::Runaway { offset, index, limit: "the StartList of another list".to_string() }));
+        }
 
         Some(Ok(cmd))
     }
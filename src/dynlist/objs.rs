@@ -1,6 +1,8 @@
 use std::{fmt};
-/// Standard object types as the same bitflags as the game.
+use dynlist::DObjType;
+
 bitflags!{
+    /// Standard object types as the same bitflags as the game.
     pub struct ObjFlag: u32 {
         const GROUPS    = 0x00000001;
         const BONES     = 0x00000002;
@@ -25,9 +27,56 @@ bitflags!{
     }
 }
 
+bitflags!{
+    /// Drawing flags in the header of every object, set with SetHeaderFlag
+    pub struct HeaderFlag: u32 {
+        const OBJ_DRAW_UNK01  = 0x01;
+        const OBJ_INVISIBLE   = 0x02;
+        const OBJ_PICKED      = 0x04;
+        const OBJ_IS_GRABBALE = 0x08;
+        const OBJ_HIGHLIGHTED = 0x10;
+    }
+}
+
+impl HeaderFlag {
+    /// Name and value of every flag, for writing them out as constants
+    pub const NAMES: &'static [(&'static str, u32)] = &[
+        ("OBJ_DRAW_UNK01",  HeaderFlag::OBJ_DRAW_UNK01.bits),
+        ("OBJ_INVISIBLE",   HeaderFlag::OBJ_INVISIBLE.bits),
+        ("OBJ_PICKED",      HeaderFlag::OBJ_PICKED.bits),
+        ("OBJ_IS_GRABBALE", HeaderFlag::OBJ_IS_GRABBALE.bits),
+        ("OBJ_HIGHLIGHTED", HeaderFlag::OBJ_HIGHLIGHTED.bits),
+    ];
+}
+
 impl fmt::Display for ObjFlag {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let comma = format!("{:?}", self).replace(" | ", ", ").to_ascii_lowercase();
         write!(f, "{}", comma)
     }
-}
\ No newline at end of file
+}
+impl ObjFlag {
+    /// The flag for an object type, which is empty for types without one
+    pub fn of(kind: DObjType) -> ObjFlag {
+        use dynlist::DObjType::*;
+        match kind {
+            D_CAR_DYNAMICS              => ObjFlag::empty(),
+            D_NET                       => ObjFlag::NETS,
+            D_JOINT | D_ANOTHER_JOINT   => ObjFlag::JOINTS,
+            D_CAMERA                    => ObjFlag::CAMERAS,
+            D_VERTEX                    => ObjFlag::VERTICES,
+            D_FACE                      => ObjFlag::FACES,
+            D_PLANE                     => ObjFlag::PLANES,
+            D_BONE                      => ObjFlag::BONES,
+            D_MATERIAL                  => ObjFlag::MATERIALS,
+            D_SHAPE                     => ObjFlag::SHAPES,
+            D_GADGET                    => ObjFlag::GADGETS,
+            D_LABEL                     => ObjFlag::LABELS,
+            D_VIEW                      => ObjFlag::VIEWS,
+            D_ANIMATOR                  => ObjFlag::ANIMATORS,
+            D_DIFF_GRP | D_GROUP        => ObjFlag::GROUPS,
+            D_PARTICLE                  => ObjFlag::PARTICLES,
+            D_LIGHT                     => ObjFlag::LIGHTS,
+        }
+    }
+}
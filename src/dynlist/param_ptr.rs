@@ -1,7 +1,7 @@
 use std::{fmt};
 use std::slice::Iter;
 
-#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
 #[allow(non_camel_case_types)]
 pub enum PtrParam {
     PARAM_OBJ_VTX = 1,
@@ -31,7 +31,7 @@ impl From<u32> for PtrParam {
         match n {
             1  => PtrParam::PARAM_OBJ_VTX,
             5  => PtrParam::PARAM_CHAR_PTR,
-            u @ _ => panic!("Unknown SetParamPtr Parameter {}", u), 
+            u => panic!("Unknown SetParamPtr Parameter {}", u), 
         }
     }
 }
\ No newline at end of file
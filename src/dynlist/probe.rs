@@ -0,0 +1,89 @@
+use byteorder::{ByteOrder, BE};
+use std::io::{self, Read, Seek, SeekFrom};
+use super::{DynCmd, CMD_SIZE, invalid_arg};
+
+/// How many commands are looked at when guessing if an offset is a dynlist
+pub const PROBE_CMDS: usize = 64;
+/// Largest float magnitude that's expected in a real list
+const MAX_FLOAT: f32 = 1.0e7;
+const START_ID: u32 = 0xD1D4;
+const STOP_ID: u32 = 58;
+
+/// A guess at how likely it is that some words are a dynlist
+#[derive(Debug, Clone)]
+pub struct Confidence {
+    /// From 0.0 (garbage) to 1.0 (looks just like a real list)
+    pub score: f32,
+    /// Why the score isn't perfect
+    pub reasons: Vec<String>,
+}
+
+impl Confidence {
+    /// Score the start of a list. Each command should be known, have valid enum arguments,
+    /// leave the words that its macro doesn't fill as zero, and have reasonable floats.
+    pub fn of(cmds: &[[u32; 6]]) -> Self {
+        // nothing after the end of the list matters
+        let cmds = match cmds.iter().position(|raw| raw[0] == STOP_ID) {
+            Some(end) => &cmds[..=end],
+            None => cmds,
+        };
+        if cmds.is_empty() {
+            return Confidence { score: 0.0, reasons: vec!["there are no commands".to_string()] };
+        }
+
+        let (mut unknown, mut invalid, mut unused, mut floats) = (0, 0, 0, 0);
+        let mut plausible = 0;
+        for raw in cmds {
+            if invalid_arg(raw).is_some() {
+                invalid += 1;
+                continue;
+            }
+            let cmd = DynCmd::from_struct(raw);
+            if let DynCmd::Unk(..) = cmd {
+                unknown += 1;
+                continue;
+            }
+            let slots = cmd.info().kind.slots();
+            let extra = (1..6).any(|w| raw[w] != 0 && !slots.contains(&w));
+            let bad_float = slots.iter()
+                .filter(|&&w| w >= 3)
+                .map(|&w| f32::from_bits(raw[w]))
+                .any(|f| !f.is_finite() || f.abs() > MAX_FLOAT);
+            if extra { unused += 1; }
+            if bad_float { floats += 1; }
+            if !extra && !bad_float { plausible += 1; }
+        }
+
+        let mut reasons = Vec::new();
+        let mut score = plausible as f32 / cmds.len() as f32;
+        if cmds[0][0] != START_ID {
+            score *= 0.75;
+            reasons.push("it doesn't start with StartList".to_string());
+        }
+        for &(n, what) in &[
+            (unknown, "are unknown"),
+            (invalid, "have an unknown enum argument"),
+            (unused, "have nonzero words that their macro doesn't use"),
+            (floats, "have huge or non-finite floats"),
+        ] {
+            if n > 0 { reasons.push(format!("{} of the first {} commands {}", n, cmds.len(), what)); }
+        }
+
+        Confidence { score, reasons }
+    }
+}
+
+/// Read up to `PROBE_CMDS` commands from `offset` and score them
+pub fn probe<R: Read + Seek>(rdr: &mut R, offset: u64) -> io::Result<Confidence> {
+    rdr.seek(SeekFrom::Start(offset))?;
+    let mut bytes = Vec::new();
+    rdr.take(PROBE_CMDS as u64 * CMD_SIZE).read_to_end(&mut bytes)?;
+    let cmds: Vec<[u32; 6]> = bytes.chunks_exact(CMD_SIZE as usize)
+        .map(|c| {
+            let mut raw = [0; 6];
+            BE::read_u32_into(c, &mut raw);
+            raw
+        })
+        .collect();
+    Ok(Confidence::of(&cmds))
+}
@@ -0,0 +1,88 @@
+use std::{fmt};
+use std::slice::Iter;
+use dynlist::DObjType;
+
+/// Make an enum of the known values of a command argument, with a table of their names
+/// that can be written out as constants
+macro_rules! value_enum {
+    ($(#[$attr:meta])* $name:ident { $($variant:ident = $val:expr,)* }) => {
+        $(#[$attr])*
+        #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+        #[allow(non_camel_case_types)]
+        pub enum $name {
+            $($variant = $val,)*
+        }
+        impl $name {
+            /// Name and value of every variant
+            pub const NAMES: &'static [(&'static str, u32)] = &[$((stringify!($variant), $val),)*];
+
+            pub fn iter() -> Iter<'static, ($name, u32)> {
+                static VARIANTS: &[($name, u32)] = &[$(($name::$variant, $val),)*];
+                VARIANTS.iter()
+            }
+            pub fn from_value(n: u32) -> Option<$name> {
+                $name::iter().find(|&&(_, v)| v == n).map(|&(e, _)| e)
+            }
+        }
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "{:?}", self)
+            }
+        }
+    }
+}
+
+value_enum!{
+    /// Colours that SetColourNum can pick
+    Colour {
+        COLOUR_BLACK = 0,
+        COLOUR_WHITE = 1,
+        COLOUR_RED = 2,
+        COLOUR_GREEN = 3,
+        COLOUR_BLUE = 4,
+        COLOUR_GRAY = 5,
+        COLOUR_DARK_GRAY = 6,
+        COLOUR_DARK_BLUE = 7,
+        COLOUR_YELLOW = 9,
+        COLOUR_PINK = 10,
+    }
+}
+
+value_enum!{
+    /// How a net is built and animated, set with SetType
+    NetType {
+        NET_TYPE_DEFAULT = 0,
+        NET_TYPE_SHAPE = 1,
+        NET_TYPE_JOINTS = 2,
+        NET_TYPE_DYNAMIC_BONES = 3,
+        NET_TYPE_JOINTS_LINKED = 4,
+        NET_TYPE_SCALED_VERTICES = 5,
+        NET_TYPE_VERTICES = 6,
+    }
+}
+
+value_enum!{
+    /// Parameters that can be set by SetParamF
+    FloatParam {
+        PARAM_F_ALPHA = 1,
+        PARAM_F_RANGE_MIN = 2,
+        PARAM_F_RANGE_MAX = 3,
+        PARAM_F_VARVAL = 6,
+    }
+}
+
+/// Every table of values, with a description for the generated constants. Joint types and
+/// control types don't have known names yet, so they're left as numbers.
+pub const VALUE_TABLES: [(&str, &[(&str, u32)]); 3] = [
+    ("Colours for the SetColourNum command", Colour::NAMES),
+    ("Net types for the SetType command", NetType::NAMES),
+    ("Parameters that can be set by the SetParamF command", FloatParam::NAMES),
+];
+
+/// The types that SetType uses for an object type, if they're known
+pub fn object_types(kind: DObjType) -> Option<&'static [(&'static str, u32)]> {
+    match kind {
+        DObjType::D_NET => Some(NetType::NAMES),
+        _ => None,
+    }
+}
@@ -0,0 +1,271 @@
+//! Relocatable big-endian MIPS ELF objects of assembled lists, so they can be linked straight
+//! into a build without assembling the macros.
+//!
+//! The lists go in `.data` and their strings in `.rodata`, laid out like `link::Layout`.
+//! Each list gets a global symbol, and every string or symbol address in a command gets an
+//! `R_MIPS_32` relocation. MIPS uses REL relocations, so the addend (the offset of a string
+//! in `.rodata`, or a list in `.data`) is stored in the command word itself.
+
+use byteorder::{ByteOrder, BE};
+use dynlist::CMD_SIZE;
+use error::Error;
+use link::Layout;
+use source::{SourceList, Target};
+use std::io::Write;
+
+const ELF_HEADER_SIZE: u32 = 52;
+const SECTION_HEADER_SIZE: u32 = 40;
+const SYMBOL_SIZE: u32 = 16;
+const REL_SIZE: u32 = 8;
+
+const ET_REL: u16 = 1;
+const EM_MIPS: u16 = 8;
+/// MIPS III, like the VR4300
+const EF_MIPS_ARCH_3: u32 = 0x2000_0000;
+
+const SHT_PROGBITS: u32 = 1;
+const SHT_SYMTAB: u32 = 2;
+const SHT_STRTAB: u32 = 3;
+const SHT_REL: u32 = 9;
+const SHF_WRITE: u32 = 1;
+const SHF_ALLOC: u32 = 2;
+
+const STB_LOCAL: u8 = 0;
+const STB_GLOBAL: u8 = 1;
+const STT_NOTYPE: u8 = 0;
+const STT_OBJECT: u8 = 1;
+const STT_SECTION: u8 = 3;
+
+const R_MIPS_32: u32 = 2;
+
+// section indices
+const DATA: u16 = 1;
+const RODATA: u16 = 2;
+const SYMTAB: u32 = 4;
+const STRTAB: u32 = 5;
+const SHSTRTAB: u16 = 6;
+// symbol indices of the section symbols
+const DATA_SYM: u32 = 1;
+const RODATA_SYM: u32 = 2;
+
+/// A NUL separated string table
+struct StrTab(Vec<u8>);
+
+impl StrTab {
+    fn new() -> Self {
+        StrTab(vec![0])
+    }
+    fn add(&mut self, s: &str) -> u32 {
+        let offset = self.0.len() as u32;
+        self.0.extend_from_slice(s.as_bytes());
+        self.0.push(0);
+        offset
+    }
+}
+
+struct Section {
+    name: u32,
+    kind: u32,
+    flags: u32,
+    data: Vec<u8>,
+    link: u32,
+    info: u32,
+    align: u32,
+    entsize: u32,
+}
+
+fn symbol(out: &mut Vec<u8>, name: u32, value: u32, size: u32, bind: u8, kind: u8, shndx: u16) {
+    let mut entry = [0; SYMBOL_SIZE as usize];
+    BE::write_u32(&mut entry[0..], name);
+    BE::write_u32(&mut entry[4..], value);
+    BE::write_u32(&mut entry[8..], size);
+    entry[12] = bind << 4 | kind;
+    BE::write_u16(&mut entry[14..], shndx);
+    out.extend_from_slice(&entry);
+}
+
+/// Write `lists` as a relocatable object. Symbols that aren't one of the lists are left
+/// undefined, for the linker to fill in.
+pub fn write<W: Write>(mut w: W, lists: &[SourceList]) -> Result<(), Error> {
+    let layout = Layout::new(lists)?;
+    let mut strtab = StrTab::new();
+    let mut symtab = Vec::new();
+    symbol(&mut symtab, 0, 0, 0, STB_LOCAL, STT_NOTYPE, 0);
+    symbol(&mut symtab, 0, 0, 0, STB_LOCAL, STT_SECTION, DATA);
+    symbol(&mut symtab, 0, 0, 0, STB_LOCAL, STT_SECTION, RODATA);
+    let first_global = symtab.len() as u32 / SYMBOL_SIZE;
+    for (name, offset, size) in &layout.lists {
+        let name = strtab.add(name);
+        symbol(&mut symtab, name, *offset, *size, STB_GLOBAL, STT_OBJECT, DATA);
+    }
+
+    let mut externs: Vec<&str> = Vec::new();
+    let mut data = Vec::with_capacity(layout.block_size as usize);
+    let mut rel = Vec::new();
+    for (i, cmd) in lists.iter().flat_map(|l| &l.cmds).enumerate() {
+        let mut raw = cmd.raw;
+        for reloc in &cmd.relocs {
+            let (sym, addend) = match reloc.target {
+                Target::Str(ref s) => (RODATA_SYM, layout.string(s).unwrap_or_default()),
+                Target::Sym(ref name) => match layout.list(name) {
+                    Some(offset) => (DATA_SYM, offset),
+                    None => {
+                        let index = match externs.iter().position(|e| e == name) {
+                            Some(index) => index,
+                            None => {
+                                externs.push(name);
+                                externs.len() - 1
+                            },
+                        };
+                        (first_global + (layout.lists.len() + index) as u32, 0)
+                    },
+                },
+            };
+            raw[reloc.word] = addend;
+            let mut entry = [0; REL_SIZE as usize];
+            BE::write_u32(&mut entry[0..], (i as u64 * CMD_SIZE) as u32 + reloc.word as u32 * 4);
+            BE::write_u32(&mut entry[4..], sym << 8 | R_MIPS_32);
+            rel.extend_from_slice(&entry);
+        }
+        let mut bytes = [0; CMD_SIZE as usize];
+        BE::write_u32_into(&raw, &mut bytes);
+        data.extend_from_slice(&bytes);
+    }
+    for name in &externs {
+        let name = strtab.add(name);
+        symbol(&mut symtab, name, 0, 0, STB_GLOBAL, STT_NOTYPE, 0);
+    }
+
+    let mut shstrtab = StrTab::new();
+    let sections = vec![
+        Section { name: shstrtab.add(".data"), kind: SHT_PROGBITS, flags: SHF_WRITE | SHF_ALLOC,
+            data, link: 0, info: 0, align: 4, entsize: 0 },
+        Section { name: shstrtab.add(".rodata"), kind: SHT_PROGBITS, flags: SHF_ALLOC,
+            data: layout.pool(), link: 0, info: 0, align: 4, entsize: 0 },
+        Section { name: shstrtab.add(".rel.data"), kind: SHT_REL, flags: 0,
+            data: rel, link: SYMTAB, info: u32::from(DATA), align: 4, entsize: REL_SIZE },
+        Section { name: shstrtab.add(".symtab"), kind: SHT_SYMTAB, flags: 0,
+            data: symtab, link: STRTAB, info: first_global, align: 4, entsize: SYMBOL_SIZE },
+        Section { name: shstrtab.add(".strtab"), kind: SHT_STRTAB, flags: 0,
+            data: strtab.0, link: 0, info: 0, align: 1, entsize: 0 },
+        Section { name: shstrtab.add(".shstrtab"), kind: SHT_STRTAB, flags: 0,
+            data: shstrtab.0, link: 0, info: 0, align: 1, entsize: 0 },
+    ];
+
+    // section contents follow the ELF header, then the section header table
+    let mut body = Vec::new();
+    let mut offsets = Vec::new();
+    for section in &sections {
+        let offset = (ELF_HEADER_SIZE + body.len() as u32).next_multiple_of(section.align);
+        body.resize((offset - ELF_HEADER_SIZE) as usize, 0);
+        offsets.push(offset);
+        body.extend_from_slice(&section.data);
+    }
+    let shoff = (ELF_HEADER_SIZE + body.len() as u32).next_multiple_of(4);
+    body.resize((shoff - ELF_HEADER_SIZE) as usize, 0);
+
+    let mut header = [0; ELF_HEADER_SIZE as usize];
+    header[..7].copy_from_slice(&[0x7F, b'E', b'L', b'F', 1, 2, 1]);
+    BE::write_u16(&mut header[16..], ET_REL);
+    BE::write_u16(&mut header[18..], EM_MIPS);
+    BE::write_u32(&mut header[20..], 1);
+    BE::write_u32(&mut header[32..], shoff);
+    BE::write_u32(&mut header[36..], EF_MIPS_ARCH_3);
+    BE::write_u16(&mut header[40..], ELF_HEADER_SIZE as u16);
+    BE::write_u16(&mut header[46..], SECTION_HEADER_SIZE as u16);
+    BE::write_u16(&mut header[48..], sections.len() as u16 + 1);
+    BE::write_u16(&mut header[50..], SHSTRTAB);
+    w.write_all(&header)?;
+    w.write_all(&body)?;
+
+    w.write_all(&[0; SECTION_HEADER_SIZE as usize])?;
+    for (section, offset) in sections.iter().zip(offsets) {
+        let fields = [section.name, section.kind, section.flags, 0, offset, section.data.len() as u32,
+            section.link, section.info, section.align, section.entsize];
+        let mut entry = [0; SECTION_HEADER_SIZE as usize];
+        BE::write_u32_into(&fields, &mut entry);
+        w.write_all(&entry)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use source::{Reloc, SourceCmd};
+
+    fn list(name: &str, targets: Vec<Target>) -> SourceList {
+        let cmds = targets.into_iter().map(|target| SourceCmd {
+            raw: [12, 0, 0, 0, 0, 0], relocs: vec![Reloc { word: 1, target }], line: 1, col: 1,
+        }).collect();
+        SourceList { name: name.to_string(), cmds }
+    }
+
+    fn section_header(obj: &[u8], index: usize) -> [u32; 10] {
+        let shoff = BE::read_u32(&obj[32..]) as usize + index * SECTION_HEADER_SIZE as usize;
+        let mut fields = [0; 10];
+        BE::read_u32_into(&obj[shoff..shoff + SECTION_HEADER_SIZE as usize], &mut fields);
+        fields
+    }
+
+    fn section_data(obj: &[u8], index: usize) -> &[u8] {
+        let header = section_header(obj, index);
+        &obj[header[4] as usize..(header[4] + header[5]) as usize]
+    }
+
+    fn name_at(table: &[u8], offset: u32) -> &str {
+        let rest = &table[offset as usize..];
+        ::std::str::from_utf8(&rest[..rest.iter().position(|&b| b == 0).unwrap()]).unwrap()
+    }
+
+    #[test]
+    fn header_sections_and_relocs() {
+        let lists = vec![
+            list("a", vec![Target::Str("hi".to_string()), Target::Str("there".to_string())]),
+            list("b", vec![Target::Sym("b".to_string()), Target::Sym("ext".to_string())]),
+        ];
+        let mut obj = Vec::new();
+        write(&mut obj, &lists).unwrap();
+
+        // 32-bit, big-endian, relocatable MIPS
+        assert_eq!(&obj[..7], &[0x7F, b'E', b'L', b'F', 1, 2, 1]);
+        assert_eq!(BE::read_u16(&obj[16..]), ET_REL);
+        assert_eq!(BE::read_u16(&obj[18..]), EM_MIPS);
+        assert_eq!(BE::read_u32(&obj[36..]), EF_MIPS_ARCH_3);
+        assert_eq!(BE::read_u16(&obj[48..]), 7);
+        assert_eq!(BE::read_u16(&obj[50..]), SHSTRTAB);
+        assert_eq!(section_header(&obj, 0), [0; 10]);
+
+        let shstrtab = section_data(&obj, SHSTRTAB as usize);
+        let names: Vec<&str> = (1..7).map(|i| name_at(shstrtab, section_header(&obj, i)[0])).collect();
+        assert_eq!(names, [".data", ".rodata", ".rel.data", ".symtab", ".strtab", ".shstrtab"]);
+        assert_eq!(section_data(&obj, 2), b"hi\0\0there\0\0\0");
+
+        // addends: "there" is 4 into .rodata, and list b is 48 into .data
+        let data = section_data(&obj, 1);
+        let addends: Vec<u32> = data.chunks(CMD_SIZE as usize).map(|c| BE::read_u32(&c[4..])).collect();
+        assert_eq!(addends, [0, 4, 48, 0]);
+
+        let rel = section_header(&obj, 3);
+        assert_eq!(&rel[6..], &[SYMTAB, u32::from(DATA), 4, REL_SIZE]);
+        let mut entries = [0; 8];
+        BE::read_u32_into(section_data(&obj, 3), &mut entries);
+        // the undefined symbol comes after the three local symbols and the two lists
+        assert_eq!(entries, [
+            4, RODATA_SYM << 8 | R_MIPS_32,
+            28, RODATA_SYM << 8 | R_MIPS_32,
+            52, DATA_SYM << 8 | R_MIPS_32,
+            76, 5 << 8 | R_MIPS_32,
+        ]);
+
+        let symtab = section_data(&obj, SYMTAB as usize);
+        let strtab = section_data(&obj, STRTAB as usize);
+        let ext = &symtab[5 * SYMBOL_SIZE as usize..6 * SYMBOL_SIZE as usize];
+        assert_eq!(name_at(strtab, BE::read_u32(ext)), "ext");
+        assert_eq!((ext[12], BE::read_u16(&ext[14..])), (STB_GLOBAL << 4 | STT_NOTYPE, 0));
+        let b = &symtab[4 * SYMBOL_SIZE as usize..5 * SYMBOL_SIZE as usize];
+        assert_eq!(name_at(strtab, BE::read_u32(b)), "b");
+        assert_eq!(BE::read_u32(&b[4..]), 48);
+        assert_eq!(BE::read_u16(&b[14..]), DATA);
+    }
+}
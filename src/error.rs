@@ -0,0 +1,110 @@
+use std::error;
+use std::fmt;
+use std::io;
+
+/// Errors that can come up while reading, writing, or building a dynlist.
+/// Offsets are file offsets, and indices count commands from the start of the list.
+/// For a list read from text, the offset is where the command goes in the binary list.
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    /// The input ended partway through a command
+    UnexpectedEof { offset: u64, index: usize, read: usize },
+    /// The input ended on a command boundary, but before a `StopList`
+    MissingStop { offset: u64, index: usize },
+    /// A command id that isn't known
+    UnknownCmd { offset: u64, index: usize, raw: [u32; 6] },
+    /// A known command with an enumerated argument (like a `DObjType`) that is out of range
+    InvalidArg { offset: u64, index: usize, raw: [u32; 6], word: usize, msg: String },
+    /// The start of the list is past the end of the input
+    OutOfRange { offset: u64, size: u64 },
+    /// The list ran into one of the iterator's `Limits` before its `StopList`
+    Runaway { offset: u64, index: usize, limit: String },
+    /// A command in a text list that can't be encoded, like an unknown name or a missing argument
+    BadCmd { offset: u64, index: usize, line: Option<usize>, msg: String },
+    /// A text input (a JSON or YAML list, a model, a manifest, or a symbol file) that can't be parsed
+    Syntax { what: &'static str, line: Option<usize>, msg: String },
+    /// A file that another input refers to, like a material library or a glTF buffer, can't be read
+    File { path: String, err: io::Error },
+    /// A list in a manifest that can't be found
+    Manifest { msg: String },
+    /// Lists and symbols that can't be laid out or resolved
+    Link { msg: String },
+    /// A list that doesn't fit where it's going in a ROM, or a pointer to it that can't be changed
+    Rom { msg: String },
+}
+
+impl Error {
+    /// File offset that the error happened at
+    pub fn offset(&self) -> Option<u64> {
+        use self::Error::*;
+        match *self {
+            UnexpectedEof { offset, .. } | MissingStop { offset, .. } | UnknownCmd { offset, .. }
+            | InvalidArg { offset, .. } | OutOfRange { offset, .. } | Runaway { offset, .. }
+            | BadCmd { offset, .. } => Some(offset),
+            Io(..) | Syntax { .. } | File { .. } | Manifest { .. } | Link { .. } | Rom { .. } => None,
+        }
+    }
+    /// Index of the command that the error happened at
+    pub fn index(&self) -> Option<usize> {
+        use self::Error::*;
+        match *self {
+            UnexpectedEof { index, .. } | MissingStop { index, .. } | UnknownCmd { index, .. }
+            | InvalidArg { index, .. } | Runaway { index, .. } | BadCmd { index, .. } => Some(index),
+            Io(..) | OutOfRange { .. } | Syntax { .. } | File { .. } | Manifest { .. } | Link { .. }
+            | Rom { .. } => None,
+        }
+    }
+    /// The raw words of the offending command, and which word is the problem
+    pub fn raw(&self) -> Option<(&[u32; 6], usize)> {
+        match self {
+            Error::UnknownCmd { raw, .. } => Some((raw, 0)),
+            Error::InvalidArg { raw, word, .. } => Some((raw, *word)),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use self::Error::*;
+        match self {
+            Io(e) => write!(f, "{}", e),
+            UnexpectedEof { offset, index, read } =>
+                write!(f, "input ended {} bytes into command {} at {:#X}", read, index, offset),
+            MissingStop { offset, index } =>
+                write!(f, "input ended at {:#X} after {} commands, without a StopList", offset, index),
+            UnknownCmd { offset, index, raw } =>
+                write!(f, "unknown dynlist command {:#x} (command {} at {:#X})", raw[0], index, offset),
+            InvalidArg { offset, index, msg, .. } =>
+                write!(f, "{} (command {} at {:#X})", msg, index, offset),
+            OutOfRange { offset, size } =>
+                write!(f, "list offset {:#X} is past the end of the {:#X} byte input", offset, size),
+            Runaway { offset, index, limit } =>
+                write!(f, "list reached {} without a StopList (command {} at {:#X})", limit, index, offset),
+            BadCmd { offset, index, line: Some(line), msg } =>
+                write!(f, "line {}: {} (command {} at {:#X})", line, msg, index, offset),
+            BadCmd { offset, index, line: None, msg } =>
+                write!(f, "{} (command {} at {:#X})", msg, index, offset),
+            Syntax { what, line: Some(line), msg } => write!(f, "{}, line {}: {}", what, line, msg),
+            Syntax { what, line: None, msg } => write!(f, "{}: {}", what, msg),
+            File { path, err } => write!(f, "{}: {}", path, err),
+            Manifest { msg } | Link { msg } | Rom { msg } => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::Io(e) | Error::File { err: e, .. } => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
@@ -0,0 +1,707 @@
+use byteorder::{ByteOrder, LE};
+use dynlist::{DObjType, Vector};
+use error::Error;
+use mesh::{Material, Mesh, Vertex};
+use scene::Scene;
+use serde_json::{self, Value};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+const FLOAT: u32 = 5126;
+const UNSIGNED_BYTE: u32 = 5121;
+const UNSIGNED_SHORT: u32 = 5123;
+const UNSIGNED_INT: u32 = 5125;
+const ARRAY_BUFFER: u32 = 34962;
+const ELEMENT_ARRAY_BUFFER: u32 = 34963;
+const MODE_POINTS: u32 = 0;
+const MODE_TRIANGLES: u32 = 4;
+
+/// The vertices and faces that make up one glTF mesh
+#[derive(Debug)]
+struct MeshSrc {
+    name: String,
+    /// The dynlist object made for this mesh, if any
+    obj: Option<usize>,
+    vertices: Vec<usize>,
+    /// Vertices and material of each face
+    faces: Vec<(Vec<usize>, Option<usize>)>,
+}
+
+/// Per vertex joint indices and weights for a skinned mesh
+type Influences = Vec<Vec<(u16, f32)>>;
+
+/// Accumulates the binary buffer, its views, and accessors
+#[derive(Debug, Default)]
+struct Buffers {
+    data: Vec<u8>,
+    views: Vec<Value>,
+    accessors: Vec<Value>,
+}
+
+impl Buffers {
+    fn view(&mut self, bytes: &[u8], target: Option<u32>) -> usize {
+        while !self.data.len().is_multiple_of(4) { self.data.push(0); }
+        let mut view = json!({
+            "buffer": 0,
+            "byteOffset": self.data.len(),
+            "byteLength": bytes.len(),
+        });
+        if let Some(t) = target { view["target"] = json!(t); }
+        self.data.extend_from_slice(bytes);
+        self.views.push(view);
+        self.views.len() - 1
+    }
+    fn accessor(&mut self, view: usize, component: u32, count: usize, kind: &str) -> usize {
+        self.accessors.push(json!({
+            "bufferView": view,
+            "componentType": component,
+            "count": count,
+            "type": kind,
+        }));
+        self.accessors.len() - 1
+    }
+    fn floats(&mut self, data: &[f32], kind: &str, width: usize, target: Option<u32>) -> usize {
+        let mut bytes = vec![0; data.len() * 4];
+        LE::write_f32_into(data, &mut bytes);
+        let view = self.view(&bytes, target);
+        self.accessor(view, FLOAT, data.len() / width, kind)
+    }
+    fn vec3s(&mut self, data: &[Vector], with_bounds: bool) -> usize {
+        let flat: Vec<f32> = data.iter().flat_map(|v| vec![v.x, v.y, v.z]).collect();
+        let acc = self.floats(&flat, "VEC3", 3, Some(ARRAY_BUFFER));
+        if with_bounds && !data.is_empty() {
+            let min = data.iter().fold([f32::MAX; 3], |m, v| [m[0].min(v.x), m[1].min(v.y), m[2].min(v.z)]);
+            let max = data.iter().fold([f32::MIN; 3], |m, v| [m[0].max(v.x), m[1].max(v.y), m[2].max(v.z)]);
+            self.accessors[acc]["min"] = json!(min);
+            self.accessors[acc]["max"] = json!(max);
+        }
+        acc
+    }
+    fn indices(&mut self, data: &[u32]) -> usize {
+        let mut bytes = vec![0; data.len() * 4];
+        LE::write_u32_into(data, &mut bytes);
+        let view = self.view(&bytes, Some(ELEMENT_ARRAY_BUFFER));
+        self.accessor(view, UNSIGNED_INT, data.len(), "SCALAR")
+    }
+    fn joints(&mut self, data: &[u16]) -> usize {
+        let mut bytes = vec![0; data.len() * 2];
+        LE::write_u16_into(data, &mut bytes);
+        let view = self.view(&bytes, Some(ARRAY_BUFFER));
+        self.accessor(view, UNSIGNED_SHORT, data.len() / 4, "VEC4")
+    }
+}
+
+/// Write a glTF 2.0 document, with an embedded buffer, of the joint hierarchy, meshes, and skin
+/// that a dynlist makes. Joint positions are treated as translations from the object they
+/// are attached to, and inverse bind matrices only account for those translations.
+/// Returns warnings about anything in the scene that couldn't be written as it is.
+pub fn write<W: Write>(w: W, scene: &Scene, name: &str) -> io::Result<Vec<String>> {
+    let mut warnings = Vec::new();
+    let mut bufs = Buffers::default();
+    let sources = mesh_sources(scene);
+
+    // nodes for joints and nets, parented by what they're attached to
+    let node_objs: Vec<usize> = scene.objs.iter()
+        .enumerate()
+        .filter(|(_, o)| o.is(DObjType::D_JOINT) || o.is(DObjType::D_NET))
+        .map(|(i, _)| i)
+        .collect();
+    let node_of: HashMap<usize, usize> = node_objs.iter().enumerate().map(|(n, &o)| (o, n)).collect();
+    let mut parents: Vec<Option<usize>> = node_objs.iter().map(|&o| scene.objs[o].attached_to
+        .and_then(|(p, _)| node_of.get(&p).cloned())
+        .filter(|&p| p != node_of[&o])
+    ).collect();
+    // a loop of attachments has no root, so break it where it comes back around
+    for start in 0..parents.len() {
+        let mut path = vec![start];
+        let mut n = start;
+        while let Some(p) = parents[n] {
+            if path.contains(&p) {
+                let (o, po) = (node_objs[n], node_objs[p]);
+                warnings.push(format!("{} is attached to {} in a loop, so it's made a root node",
+                    scene.objs[o].name(o), scene.objs[po].name(po)));
+                parents[n] = None;
+                break;
+            }
+            path.push(p);
+            n = p;
+        }
+    }
+    let local: Vec<Vector> = node_objs.iter().enumerate().map(|(n, &o)| {
+        let obj = &scene.objs[o];
+        let pos = obj.position().unwrap_or(Vector::ZERO);
+        match parents[n].map(|p| scene.objs[node_objs[p]].position().unwrap_or(Vector::ZERO)) {
+            Some(ppos) => obj.rel_pos.unwrap_or(Vector { x: pos.x - ppos.x, y: pos.y - ppos.y, z: pos.z - ppos.z }),
+            None => pos,
+        }
+    }).collect();
+    let global = |mut n: usize| {
+        let mut sum = Vector::ZERO;
+        for _ in 0..node_objs.len() {
+            sum = Vector { x: sum.x + local[n].x, y: sum.y + local[n].y, z: sum.z + local[n].z };
+            match parents[n] { Some(p) => n = p, None => break }
+        }
+        sum
+    };
+
+    let mut nodes: Vec<Value> = node_objs.iter().enumerate().map(|(n, &o)| {
+        let obj = &scene.objs[o];
+        let mut node = json!({ "name": obj.name(o), "translation": [local[n].x, local[n].y, local[n].z] });
+        if let Some(r) = obj.rotation { node["rotation"] = json!(euler_to_quat(r)); }
+        if let Some(s) = obj.scale { node["scale"] = json!([s.x, s.y, s.z]); }
+        node
+    }).collect();
+    for (n, parent) in parents.iter().enumerate() {
+        if let Some(p) = *parent {
+            let children = nodes[p].as_object_mut().unwrap()
+                .entry("children").or_insert_with(|| json!([]));
+            children.as_array_mut().unwrap().push(json!(n));
+        }
+    }
+    let mut roots: Vec<usize> = (0..nodes.len()).filter(|&n| parents[n].is_none()).collect();
+
+    // skins for nets with a skin shape, using every joint that has weights
+    let skin_joints: Vec<usize> = node_objs.iter()
+        .enumerate()
+        .filter(|(_, &o)| !scene.objs[o].weights.is_empty())
+        .map(|(n, _)| n)
+        .collect();
+    let mut skins = Vec::new();
+    let mut skinned: HashMap<usize, (usize, Influences)> = HashMap::new();
+    for (o, net) in scene.of_kind(DObjType::D_NET) {
+        let shape = net.skin_shape.and_then(|id| scene.get(id));
+        let mesh = match sources.iter().position(|m| m.obj.is_some() && m.obj == shape) {
+            Some(m) if !skin_joints.is_empty() => m,
+            _ => continue,
+        };
+        if let Some(&(first, _)) = skinned.get(&mesh) {
+            // every skin has the same joints, so the weights are the same too
+            warnings.push(format!("{} has the same skin shape as {}, so they share a mesh and skin",
+                net.name(o), scene.objs[first].name(first)));
+            nodes[node_of[&o]]["mesh"] = json!(mesh);
+            nodes[node_of[&o]]["skin"] = nodes[node_of[&first]]["skin"].clone();
+            continue;
+        }
+        let local_idx: HashMap<usize, usize> = sources[mesh].vertices.iter().enumerate().map(|(l, &v)| (v, l)).collect();
+        let skin_vtx = shape.and_then(|s| scene.objs[s].node_group)
+            .map(|g| scene.group_members(g, DObjType::D_VERTEX))
+            .unwrap_or_default();
+        let mut influences = vec![Vec::new(); sources[mesh].vertices.len()];
+        for (j, &n) in skin_joints.iter().enumerate() {
+            for &(v, weight) in &scene.objs[node_objs[n]].weights {
+                if let Some(l) = skin_vtx.get(v as usize).and_then(|v| local_idx.get(v)) {
+                    influences[*l].push((j as u16, weight));
+                }
+            }
+        }
+        skinned.insert(mesh, (o, influences));
+
+        let ibm: Vec<f32> = skin_joints.iter().flat_map(|&n| {
+            let g = global(n);
+            vec![1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, -g.x, -g.y, -g.z, 1.0]
+        }).collect();
+        let ibm = bufs.floats(&ibm, "MAT4", 16, None);
+        nodes[node_of[&o]]["mesh"] = json!(mesh);
+        nodes[node_of[&o]]["skin"] = json!(skins.len());
+        skins.push(json!({ "name": net.name(o), "joints": skin_joints, "inverseBindMatrices": ibm }));
+    }
+
+    let mut materials = Vec::new();
+    let mut material_of = HashMap::new();
+    for (i, mtl) in scene.of_kind(DObjType::D_MATERIAL) {
+        let d = mtl.diffuse.or(mtl.ambient).unwrap_or(Vector { x: 1.0, y: 1.0, z: 1.0 });
+        material_of.insert(i, materials.len());
+        materials.push(json!({
+            "name": mtl.name(i),
+            "pbrMetallicRoughness": { "baseColorFactor": [d.x, d.y, d.z, 1.0], "metallicFactor": 0.0 },
+        }));
+    }
+
+    let mut meshes = Vec::new();
+    for (m, src) in sources.iter().enumerate() {
+        meshes.push(write_mesh(&mut bufs, scene, src, skinned.get(&m).map(|(_, inf)| inf), &material_of));
+        if !skinned.contains_key(&m) {
+            nodes.push(json!({ "name": src.name, "mesh": m }));
+            roots.push(nodes.len() - 1);
+        }
+    }
+
+    let mut doc = json!({
+        "asset": { "version": "2.0", "generator": "dynlistdump" },
+        "scene": 0,
+        "scenes": [{ "name": name, "nodes": roots }],
+        "nodes": nodes,
+    });
+    if !meshes.is_empty() { doc["meshes"] = json!(meshes); }
+    if !materials.is_empty() { doc["materials"] = json!(materials); }
+    if !skins.is_empty() { doc["skins"] = json!(skins); }
+    if !bufs.data.is_empty() {
+        doc["buffers"] = json!([{
+            "byteLength": bufs.data.len(),
+            "uri": format!("data:application/octet-stream;base64,{}", base64(&bufs.data)),
+        }]);
+        doc["bufferViews"] = json!(bufs.views);
+        doc["accessors"] = json!(bufs.accessors);
+    }
+    serde_json::to_writer_pretty(w, &doc)?;
+    Ok(warnings)
+}
+
+fn write_mesh(
+    bufs: &mut Buffers,
+    scene: &Scene,
+    src: &MeshSrc,
+    skin: Option<&Influences>,
+    material_of: &HashMap<usize, usize>
+) -> Value {
+    let verts: Vec<_> = src.vertices.iter().map(|&v| &scene.objs[v]).collect();
+    let mut attrs = json!({
+        "POSITION": bufs.vec3s(&verts.iter().map(|v| v.position().unwrap_or(Vector::ZERO)).collect::<Vec<_>>(), true),
+    });
+    if verts.iter().all(|v| v.normal.is_some()) {
+        attrs["NORMAL"] = json!(bufs.vec3s(&verts.iter().filter_map(|v| v.normal).collect::<Vec<_>>(), false));
+    }
+    if verts.iter().all(|v| v.st.is_some()) {
+        let st: Vec<f32> = verts.iter().filter_map(|v| v.st).flat_map(|(s, t)| vec![s, t]).collect();
+        attrs["TEXCOORD_0"] = json!(bufs.floats(&st, "VEC2", 2, Some(ARRAY_BUFFER)));
+    }
+    if let Some(influences) = skin {
+        let mut joints = Vec::new();
+        let mut weights = Vec::new();
+        for inf in influences {
+            let mut inf = inf.clone();
+            inf.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(::std::cmp::Ordering::Equal));
+            inf.truncate(4);
+            let total: f32 = inf.iter().map(|&(_, w)| w).sum();
+            if total <= 0.0 { inf = vec![(0, 1.0)]; }
+            let total = if total > 0.0 { total } else { 1.0 };
+            inf.resize(4, (0, 0.0));
+            joints.extend(inf.iter().map(|&(j, _)| j));
+            weights.extend(inf.iter().map(|&(_, w)| w / total));
+        }
+        attrs["JOINTS_0"] = json!(bufs.joints(&joints));
+        attrs["WEIGHTS_0"] = json!(bufs.floats(&weights, "VEC4", 4, Some(ARRAY_BUFFER)));
+    }
+
+    let local: HashMap<usize, u32> = src.vertices.iter().enumerate().map(|(l, &v)| (v, l as u32)).collect();
+    let mut by_mtl: Vec<(Option<usize>, Vec<u32>)> = Vec::new();
+    for (face, mtl) in &src.faces {
+        let idx = match by_mtl.iter().position(|(m, _)| m == mtl) {
+            Some(i) => i,
+            None => { by_mtl.push((*mtl, Vec::new())); by_mtl.len() - 1 },
+        };
+        // fan triangulate anything with more than three vertices
+        for k in 1..face.len().saturating_sub(1) {
+            by_mtl[idx].1.extend([face[0], face[k], face[k + 1]].iter().map(|v| local[v]));
+        }
+    }
+    let prims: Vec<Value> = if by_mtl.is_empty() {
+        vec![json!({ "attributes": attrs, "mode": MODE_POINTS })]
+    } else {
+        by_mtl.iter().map(|(mtl, tris)| {
+            let mut prim = json!({ "attributes": attrs, "indices": bufs.indices(tris), "mode": MODE_TRIANGLES });
+            if let Some(m) = mtl.and_then(|m| material_of.get(&m)) { prim["material"] = json!(m); }
+            prim
+        }).collect()
+    };
+    json!({ "name": src.name, "primitives": prims })
+}
+
+/// Collect meshes from shapes, then from any faces that aren't part of a shape.
+/// If the list has no faces at all, its vertices become a point cloud.
+fn mesh_sources(scene: &Scene) -> Vec<MeshSrc> {
+    let mut sources = Vec::new();
+    let mut claimed = Vec::new();
+    for (s, shape) in scene.of_kind(DObjType::D_SHAPE) {
+        let mut vertices = shape.node_group
+            .map(|g| scene.group_members(g, DObjType::D_VERTEX))
+            .unwrap_or_default();
+        let faces: Vec<usize> = shape.plane_group
+            .map(|g| scene.group_members(g, DObjType::D_FACE))
+            .unwrap_or_default();
+        claimed.extend(faces.iter().cloned());
+        let faces: Vec<(Vec<usize>, Option<usize>)> = faces.into_iter()
+            .filter_map(|f| scene.face_vertices(f).ok().map(|v| (v, scene.face_material(f))))
+            .collect();
+        // faces can map their vertices from a group other than the shape's node group
+        for (verts, _) in &faces {
+            for &v in verts {
+                if !vertices.contains(&v) { vertices.push(v); }
+            }
+        }
+        if !vertices.is_empty() {
+            sources.push(MeshSrc { name: shape.name(s), obj: Some(s), vertices, faces });
+        }
+    }
+
+    let mut loose: Vec<(Option<usize>, MeshSrc)> = Vec::new();
+    for (f, _) in scene.of_kind(DObjType::D_FACE).filter(|(f, _)| !claimed.contains(f)) {
+        let verts = match scene.face_vertices(f) { Ok(v) => v, Err(_) => continue };
+        let group = scene.parent_group(f);
+        let idx = match loose.iter().position(|(g, _)| *g == group) {
+            Some(i) => i,
+            None => {
+                let name = group.map(|g| scene.objs[g].name(g)).unwrap_or_else(|| "faces".to_string());
+                loose.push((group, MeshSrc { name, obj: None, vertices: Vec::new(), faces: Vec::new() }));
+                loose.len() - 1
+            },
+        };
+        let mesh = &mut loose[idx].1;
+        for &v in &verts {
+            if !mesh.vertices.contains(&v) { mesh.vertices.push(v); }
+        }
+        mesh.faces.push((verts, scene.face_material(f)));
+    }
+    sources.extend(loose.into_iter().map(|(_, m)| m));
+
+    if sources.is_empty() {
+        let vertices: Vec<usize> = scene.of_kind(DObjType::D_VERTEX).map(|(i, _)| i).collect();
+        if !vertices.is_empty() {
+            sources.push(MeshSrc { name: "vertices".to_string(), obj: None, vertices, faces: Vec::new() });
+        }
+    }
+    sources
+}
+
+/// Convert XYZ euler angles in degrees into a [x, y, z, w] quaternion
+fn euler_to_quat(r: Vector) -> [f32; 4] {
+    let (sx, cx) = (r.x.to_radians() / 2.0).sin_cos();
+    let (sy, cy) = (r.y.to_radians() / 2.0).sin_cos();
+    let (sz, cz) = (r.z.to_radians() / 2.0).sin_cos();
+    [
+        sx * cy * cz - cx * sy * sz,
+        cx * sy * cz + sx * cy * sz,
+        cx * cy * sz - sx * sy * cz,
+        cx * cy * cz + sx * sy * sz,
+    ]
+}
+
+fn base64(data: &[u8]) -> String {
+    const TABLE: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let b = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let n = (u32::from(b[0]) << 16) | (u32::from(b[1]) << 8) | u32::from(b[2]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(TABLE[(n >> (18 - 6 * i) & 0x3F) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+/// Read every triangle mesh in a glTF (`.gltf` or binary `.glb`) file into one mesh.
+/// Node transforms aren't applied, so meshes come out in their own model space.
+/// External buffers are looked for relative to `dir`.
+pub fn read(data: &[u8], dir: &Path) -> Result<Mesh, Error> {
+    let syntax = |msg: String| Error::Syntax { what: "glTF", line: None, msg };
+    let (doc, glb_bin): (Value, Option<&[u8]>) = if data.starts_with(b"glTF") {
+        let (json, bin) = glb_chunks(data).map_err(syntax)?;
+        (serde_json::from_slice(json).map_err(|e| syntax(format!("JSON chunk: {}", e)))?, bin)
+    } else {
+        (serde_json::from_slice(data).map_err(|e| syntax(e.to_string()))?, None)
+    };
+
+    let mut buffers = Vec::new();
+    for (i, buf) in array(&doc["buffers"]).iter().enumerate() {
+        let bytes = match buf["uri"].as_str() {
+            Some(uri) if uri.starts_with("data:") => {
+                let encoded = uri.split(";base64,").nth(1)
+                    .ok_or_else(|| syntax(format!("buffer {} has a data uri that isn't base64", i)))?;
+                base64_decode(encoded).ok_or_else(|| syntax(format!("buffer {} has invalid base64 data", i)))?
+            },
+            Some(uri) => fs::read(dir.join(uri)).map_err(|err| Error::File { path: uri.to_string(), err })?,
+            None => glb_bin.ok_or_else(|| syntax(format!("buffer {} has no uri or GLB chunk", i)))?.to_vec(),
+        };
+        buffers.push(bytes);
+    }
+    let gltf = Document { doc: &doc, buffers };
+
+    let mut mesh = Mesh::default();
+    for (i, m) in array(&doc["materials"]).iter().enumerate() {
+        let name = m["name"].as_str().map(String::from).unwrap_or_else(|| format!("material_{}", i));
+        let diffuse = m["pbrMetallicRoughness"]["baseColorFactor"].as_array()
+            .filter(|c| c.len() >= 3)
+            .map(|c| {
+                let c: Vec<f32> = c.iter().map(|v| v.as_f64().unwrap_or(1.0) as f32).collect();
+                Vector { x: c[0], y: c[1], z: c[2] }
+            });
+        // pushed in order, so material numbers line up with the glTF's material indices
+        mesh.materials.push(Material { name, ambient: None, diffuse });
+    }
+    let material_of = |prim: &Value| prim["material"].as_u64().map(|m| m as usize);
+    let mut shared = HashMap::new();
+
+    for (m, gmesh) in array(&doc["meshes"]).iter().enumerate() {
+        for (p, prim) in array(&gmesh["primitives"]).iter().enumerate() {
+            let at = |e: String| syntax(format!("mesh {} primitive {}: {}", m, p, e));
+            let mode = prim["mode"].as_u64().unwrap_or(u64::from(MODE_TRIANGLES)) as u32;
+            if mode != MODE_TRIANGLES { continue; }
+            let attrs = &prim["attributes"];
+            let pos = gltf.floats(&attrs["POSITION"], 3).map_err(at)?
+                .ok_or_else(|| at("no POSITION".to_string()))?;
+            let normals = gltf.floats(&attrs["NORMAL"], 3).map_err(at)?;
+            let st = gltf.floats(&attrs["TEXCOORD_0"], 2).map_err(at)?;
+
+            // primitives that share their attributes also share their vertices
+            let key = ["POSITION", "NORMAL", "TEXCOORD_0"].iter().map(|a| attrs[a].as_u64()).collect::<Vec<_>>();
+            let next = mesh.vertices.len() as u32;
+            let base = *shared.entry(key).or_insert(next);
+            if base == next {
+                for (v, xyz) in pos.iter().enumerate() {
+                    mesh.vertices.push(Vertex {
+                        pos: Vector { x: xyz[0], y: xyz[1], z: xyz[2] },
+                        normal: normals.as_ref().and_then(|n| n.get(v)).map(|n| Vector { x: n[0], y: n[1], z: n[2] }),
+                        st: st.as_ref().and_then(|t| t.get(v)).map(|t| (t[0], t[1])),
+                    });
+                }
+            }
+            let indices = match gltf.ints(&prim["indices"]).map_err(at)? {
+                Some(idx) => idx,
+                None => (0..pos.len() as u32).collect(),
+            };
+            if let Some(&bad) = indices.iter().find(|&&i| i as usize >= pos.len()) {
+                return Err(at(format!("out of range vertex {}", bad)));
+            }
+            for tri in indices.chunks(3).filter(|t| t.len() == 3) {
+                mesh.add_polygon(&[base + tri[0], base + tri[1], base + tri[2]], material_of(prim));
+            }
+        }
+    }
+    Ok(mesh)
+}
+
+/// The bytes of each element of an accessor, and its component type
+type Elements<'a> = (Vec<&'a [u8]>, u32);
+
+/// A parsed glTF document and the contents of its buffers
+struct Document<'a> {
+    doc: &'a Value,
+    buffers: Vec<Vec<u8>>,
+}
+
+impl<'a> Document<'a> {
+    fn elements(&self, accessor: &Value, width: usize) -> Result<Option<Elements<'_>>, String> {
+        let acc = match accessor.as_u64() {
+            Some(a) => &self.doc["accessors"][a as usize],
+            None => return Ok(None),
+        };
+        let component = acc["componentType"].as_u64().unwrap_or(0) as u32;
+        let size = match component {
+            UNSIGNED_BYTE => 1,
+            UNSIGNED_SHORT => 2,
+            FLOAT | UNSIGNED_INT => 4,
+            c => return Err(format!("unsupported accessor component type {}", c)),
+        };
+        let count = acc["count"].as_u64().unwrap_or(0) as usize;
+        let view = &self.doc["bufferViews"][acc["bufferView"].as_u64()
+            .ok_or("sparse or empty accessors aren't supported")? as usize];
+        let buffer = self.buffers.get(view["buffer"].as_u64().unwrap_or(0) as usize)
+            .ok_or("accessor uses a missing buffer")?;
+        let start = (view["byteOffset"].as_u64().unwrap_or(0) + acc["byteOffset"].as_u64().unwrap_or(0)) as usize;
+        let elem = size * width;
+        let stride = view["byteStride"].as_u64().map(|s| s as usize).unwrap_or(elem);
+        let mut out = Vec::with_capacity(count);
+        for i in 0..count {
+            let at = start + i * stride;
+            out.push(buffer.get(at..at + elem).ok_or("accessor runs past the end of its buffer")?);
+        }
+        Ok(Some((out, component)))
+    }
+    fn floats(&self, accessor: &Value, width: usize) -> Result<Option<Vec<Vec<f32>>>, String> {
+        match self.elements(accessor, width)? {
+            Some((elems, FLOAT)) => Ok(Some(elems.iter()
+                .map(|e| e.chunks(4).map(LE::read_f32).collect())
+                .collect())),
+            Some((_, c)) => Err(format!("expected float attribute data, found component type {}", c)),
+            None => Ok(None),
+        }
+    }
+    fn ints(&self, accessor: &Value) -> Result<Option<Vec<u32>>, String> {
+        Ok(self.elements(accessor, 1)?.map(|(elems, component)| elems.iter()
+            .map(|e| match component {
+                UNSIGNED_BYTE  => u32::from(e[0]),
+                UNSIGNED_SHORT => u32::from(LE::read_u16(e)),
+                _              => LE::read_u32(e),
+            })
+            .collect()))
+    }
+}
+
+fn array(v: &Value) -> &[Value] {
+    v.as_array().map(Vec::as_slice).unwrap_or(&[])
+}
+
+/// Split a binary glTF into its JSON chunk and optional BIN chunk
+fn glb_chunks(data: &[u8]) -> Result<(&[u8], Option<&[u8]>), String> {
+    const JSON: u32 = 0x4E4F_534A;
+    const BIN: u32 = 0x004E_4942;
+    let mut json = None;
+    let mut bin = None;
+    let mut at = 12;
+    while at + 8 <= data.len() {
+        let len = LE::read_u32(&data[at..]) as usize;
+        let kind = LE::read_u32(&data[at + 4..]);
+        let chunk = data.get(at + 8..at + 8 + len).ok_or("truncated GLB chunk")?;
+        match kind {
+            JSON => json = Some(chunk),
+            BIN  => bin = Some(chunk),
+            _ => (),
+        }
+        at += 8 + len;
+    }
+    Ok((json.ok_or("GLB file has no JSON chunk")?, bin))
+}
+
+fn base64_decode(text: &str) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(text.len() / 4 * 3);
+    let (mut acc, mut bits) = (0u32, 0);
+    for c in text.bytes().take_while(|&c| c != b'=') {
+        let v = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => return None,
+        };
+        acc = (acc << 6) | u32::from(v);
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((acc >> bits) as u8);
+            acc &= (1 << bits) - 1;
+        }
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dynlist::{DynCmd, DynId, DynListItem, Ptr, PtrParam};
+    use dynlist::DynCmd::*;
+
+    /// Two groups of three vertices, and a shape whose one face maps its vertices from the second
+    fn shape_cmds() -> Vec<DynCmd> {
+        let mut cmds = vec![Start, UseIntId(true)];
+        for g in 1..3 {
+            cmds.push(StartGroup(DynId(g)));
+            for i in 0..3 {
+                cmds.push(MakeVtx(Vector { x: i as f32, y: g as f32, z: 0.0 }));
+            }
+            cmds.push(EndGroup(DynId(g)));
+        }
+        cmds.extend(vec![
+            StartGroup(DynId(3)),
+            MakeObj(DObjType::D_FACE, DynId(10)),
+            ParamPtr(PtrParam::PARAM_OBJ_VTX, Ptr(0)),
+            ParamPtr(PtrParam::PARAM_OBJ_VTX, Ptr(2)),
+            ParamPtr(PtrParam::PARAM_OBJ_VTX, Ptr(1)),
+            EndGroup(DynId(3)),
+            UseObj(DynId(3)),
+            MapVtx(DynId(2)),
+            MakeObj(DObjType::D_SHAPE, DynId(11)),
+            SetNodeGroup(DynId(1)),
+            SetPlaneGroup(DynId(3)),
+        ]);
+        cmds
+    }
+
+    fn export(mut cmds: Vec<DynCmd>) -> (Value, Vec<String>) {
+        cmds.push(Stop);
+        let items = cmds.into_iter().map(|c| Ok(DynListItem::from_cmd(c)));
+        let scene = Scene::from_items(items, 0).unwrap();
+        let mut out = Vec::new();
+        let warnings = write(&mut out, &scene, "test").unwrap();
+        (serde_json::from_slice(&out).unwrap(), warnings)
+    }
+
+    fn accessor_bytes(doc: &Value, acc: &Value) -> Vec<u8> {
+        let acc = &doc["accessors"][acc.as_u64().unwrap() as usize];
+        let view = &doc["bufferViews"][acc["bufferView"].as_u64().unwrap() as usize];
+        let uri = doc["buffers"][0]["uri"].as_str().unwrap();
+        let data = base64_decode(uri.split(',').nth(1).unwrap()).unwrap();
+        let start = view["byteOffset"].as_u64().unwrap() as usize;
+        data[start..start + view["byteLength"].as_u64().unwrap() as usize].to_vec()
+    }
+
+    fn joint(id: u32, pos: Vector, parent: Option<u32>) -> Vec<DynCmd> {
+        let mut cmds = vec![MakeObj(DObjType::D_JOINT, DynId(id)), SetInitPos(pos)];
+        cmds.extend(parent.map(|p| Attach(DynId(p))));
+        cmds
+    }
+
+    #[test]
+    fn faces_mapped_from_another_vertex_group() {
+        let mut cmds = shape_cmds();
+        cmds.extend(joint(20, Vector { x: 1.0, y: 0.0, z: 0.0 }, None));
+        cmds.extend(joint(21, Vector { x: 1.0, y: 2.0, z: 0.0 }, Some(20)));
+        let (doc, warnings) = export(cmds);
+        assert!(warnings.is_empty());
+
+        let nodes = doc["nodes"].as_array().unwrap();
+        let names: Vec<&str> = nodes.iter().map(|n| n["name"].as_str().unwrap()).collect();
+        assert_eq!(names, ["joint_14", "joint_15", "shape_b"]);
+        assert_eq!(nodes[0]["children"], json!([1]));
+        assert_eq!(nodes[1]["translation"], json!([0.0, 2.0, 0.0]));
+        assert_eq!(nodes[2]["mesh"], json!(0));
+        assert_eq!(doc["scenes"][0]["nodes"], json!([0, 2]));
+
+        // the node group's three vertices, then the three that the face mapped in, in its order
+        let prim = &doc["meshes"][0]["primitives"][0];
+        let pos = &prim["attributes"]["POSITION"];
+        assert_eq!(doc["accessors"][pos.as_u64().unwrap() as usize]["count"], json!(6));
+        let xy: Vec<(f32, f32)> = accessor_bytes(&doc, pos).chunks(12)
+            .map(|v| (LE::read_f32(v), LE::read_f32(&v[4..])))
+            .collect();
+        assert_eq!(xy, [(0.0, 1.0), (1.0, 1.0), (2.0, 1.0), (0.0, 2.0), (2.0, 2.0), (1.0, 2.0)]);
+
+        let mut indices = [0; 3];
+        LE::read_u32_into(&accessor_bytes(&doc, &prim["indices"]), &mut indices);
+        assert_eq!(indices, [3, 4, 5]);
+    }
+
+    #[test]
+    fn attach_cycle_still_has_a_root() {
+        let mut cmds = shape_cmds();
+        cmds.extend(joint(20, Vector::ZERO, None));
+        cmds.extend(joint(21, Vector::ZERO, Some(20)));
+        cmds.extend(vec![UseObj(DynId(20)), Attach(DynId(21))]);
+        let (doc, warnings) = export(cmds);
+        assert_eq!(warnings.len(), 1);
+
+        let nodes = doc["nodes"].as_array().unwrap();
+        assert_eq!(doc["scenes"][0]["nodes"], json!([1, 2]));
+        assert_eq!(nodes[1]["children"], json!([0]));
+        assert!(nodes[0].get("children").is_none());
+    }
+
+    #[test]
+    fn nets_sharing_a_skin_shape() {
+        let mut cmds = shape_cmds();
+        cmds.extend(joint(20, Vector::ZERO, None));
+        cmds.push(SetSkinWgt(0, 1.0));
+        for id in 30..32 {
+            cmds.extend(vec![MakeObj(DObjType::D_NET, DynId(id)), SetSkinShape(DynId(11))]);
+        }
+        let (doc, warnings) = export(cmds);
+        assert_eq!(warnings.len(), 1);
+
+        // the skinned mesh only hangs off the nets, so there's no node of its own
+        let nodes = doc["nodes"].as_array().unwrap();
+        assert_eq!(nodes.len(), 3);
+        for net in &nodes[1..] {
+            assert_eq!(net["mesh"], json!(0));
+            assert_eq!(net["skin"], json!(0));
+        }
+        assert_eq!(doc["skins"].as_array().unwrap().len(), 1);
+        let attrs = &doc["meshes"][0]["primitives"][0]["attributes"];
+        assert_eq!(doc["accessors"][attrs["JOINTS_0"].as_u64().unwrap() as usize]["count"], json!(6));
+    }
+}
@@ -0,0 +1,220 @@
+use dynlist::{self, DynCmd, DynListItem, ArgVal, DObjType, CMD_SIZE};
+use serde_json::{self, Value, Number};
+use error::Error;
+use std::io::Read;
+
+/// Convert a single decoded command into a JSON object. `obj` is the type of the current
+/// object, which is needed to name the type that SetType sets.
+pub fn item_to_value(item: &DynListItem, index: usize, offset: u64, obj: Option<DObjType>) -> Value {
+    let info = item.info();
+    let flags = dynlist::cmd_flags(item.cmd());
+    let named = item.cmd().symbolic_arg(obj);
+    let args: Vec<_> = item.cmd().args().iter().enumerate().map(|(i, a)| {
+        let mut value = arg_to_value(a, flags);
+        match (a, &named) {
+            (ArgVal::Int(_), Some((at, name))) if *at == i => value["name"] = json!(name),
+            _ => (),
+        }
+        value
+    }).collect();
+    let mut obj = json!({
+        "index": index,
+        "offset": offset,
+        "name": info.base,
+        "id": info.id,
+        "args": args,
+        "raw": item.raw(),
+    });
+    if !info.objs.is_empty() {
+        let objs: Vec<String> = if info.objs.is_all() {
+            vec!["all".to_string()]
+        } else {
+            info.objs.to_string().split(", ").map(String::from).collect()
+        };
+        obj["objs"] = json!(objs);
+    }
+    obj
+}
+
+fn arg_to_value(arg: &ArgVal, flags: Option<&[(&str, u32)]>) -> Value {
+    use self::ArgVal::*;
+    if let (Flag(n), Some(names)) = (arg, flags) {
+        return json!({"type": arg.kind(), "value": n, "names": dynlist::flag_names(*n, names)});
+    }
+    let value = match arg {
+        Bool(b)   => json!(b),
+        Int(n) | Flag(n) => json!(n),
+        Float(f)  => float_value(*f),
+        Vec(v)    => json!([float_value(v.x), float_value(v.y), float_value(v.z)]),
+        Id(id)    => json!(id.0),
+        Ptr(p)    => json!(p.0),
+        Obj(t)    => json!(t.to_string()),
+        Param(p)  => json!(p.to_string()),
+    };
+    json!({"type": arg.kind(), "value": value})
+}
+
+/// Go through the shortest decimal form of the f32 so that `0.1` doesn't become `0.10000000149011612`
+fn float_value(f: f32) -> Value {
+    f.to_string().parse::<f64>().ok()
+        .and_then(Number::from_f64)
+        .map(Value::Number)
+        .unwrap_or(Value::Null)
+}
+
+/// Read a JSON dynlist, as produced by `dump --format json`, and encode every command.
+/// Commands are looked up by `name`, and their `args` are placed into the raw words
+/// in the same way as the generated macros. Commands without `args` use their `raw` words.
+pub fn import<R: Read>(rdr: R) -> Result<Vec<DynListItem>, Error> {
+    let syntax = |msg: String| Error::Syntax { what: "JSON dynlist", line: None, msg };
+    let doc: Value = serde_json::from_reader(rdr).map_err(|e| syntax(e.to_string()))?;
+    let cmds = match doc {
+        Value::Array(ref cmds) => cmds,
+        Value::Object(ref obj) => match obj.get("commands") {
+            Some(Value::Array(cmds)) => cmds,
+            _ => return Err(syntax("object is missing a \"commands\" array".to_string())),
+        },
+        _ => return Err(syntax("should be an array of commands or an object with a \"commands\" array".to_string())),
+    };
+
+    cmds.iter()
+        .enumerate()
+        .map(|(index, cmd)| {
+            let offset = index as u64 * CMD_SIZE;
+            let raw = import_cmd(cmd).map_err(|msg| Error::BadCmd { offset, index, line: None, msg })?;
+            DynListItem::decode(raw, offset, index)
+        })
+        .collect()
+}
+
+fn import_cmd(cmd: &Value) -> Result<[u32; 6], String> {
+    let args = cmd.get("args").and_then(Value::as_array);
+    let raw = match (cmd.get("name").and_then(Value::as_str), args) {
+        (Some(name), Some(args)) => {
+            let info = DynCmd::lookup(name)
+                .ok_or_else(|| format!("unknown command \"{}\"", name))?;
+            let scalars = args.iter().fold(Vec::new(), |mut v, a| { flatten(a, &mut v); v });
+            let slots = info.kind.slots();
+            if scalars.len() != slots.len() {
+                return Err(format!("{} takes {} values, but {} were provided", name, slots.len(), scalars.len()));
+            }
+            let mut raw = [0; 6];
+            raw[0] = info.id;
+            for (&slot, val) in slots.iter().zip(scalars) {
+                raw[slot] = if slot < 3 { int_word(val)? } else { float_word(val)? };
+            }
+            raw
+        },
+        _ => {
+            let words = cmd.get("raw").and_then(Value::as_array)
+                .ok_or("command needs either \"name\" and \"args\", or \"raw\"")?;
+            if words.len() != 6 { return Err(format!("\"raw\" should have 6 words, not {}", words.len())); }
+            let mut raw = [0; 6];
+            for (r, w) in raw.iter_mut().zip(words) {
+                *r = int_word(w)?;
+            }
+            raw
+        },
+    };
+    Ok(raw)
+}
+
+/// Collect all of the scalar values from a (possibly typed) argument. A flag can be given
+/// by its `names` instead of its `value`.
+fn flatten<'a>(arg: &'a Value, out: &mut Vec<&'a Value>) {
+    match arg {
+        Value::Object(obj) => match (obj.get("value"), obj.get("names")) {
+            (Some(v), _) => flatten(v, out),
+            (None, Some(names)) => out.push(names),
+            (None, None) => (),
+        },
+        Value::Array(vals) => for v in vals { flatten(v, out) },
+        _ => out.push(arg),
+    }
+}
+
+fn int_word(val: &Value) -> Result<u32, String> {
+    match val {
+        Value::Bool(b) => Ok(*b as u32),
+        Value::Number(n) => n.as_u64().filter(|&n| n <= u64::from(u32::MAX)).map(|n| n as u32)
+            .or_else(|| n.as_i64().filter(|&n| n >= i64::from(i32::MIN)).map(|n| n as u32))
+            .ok_or_else(|| format!("{} is not a 32-bit integer", n)),
+        Value::String(s) => dynlist::parse_int_expr(s)
+            .ok_or_else(|| format!("unknown constant \"{}\"", s)),
+        Value::Array(names) => names.iter().try_fold(0, |acc, name| Ok(acc | int_word(name)?)),
+        _ => Err(format!("expected an integer, found {}", val)),
+    }
+}
+
+fn float_word(val: &Value) -> Result<u32, String> {
+    val.as_f64()
+        .map(|f| (f as f32).to_bits())
+        .ok_or_else(|| format!("expected a float, found {}", val))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dump::{self, Config};
+    use dynlist::{DynId, NetType, Ptr, PtrParam, Vector};
+    use dynlist::DynCmd::*;
+
+    fn dumped(cmds: Vec<DynCmd>) -> Vec<u8> {
+        let items = cmds.into_iter().map(|c| Ok(DynListItem::from_cmd(c)));
+        let mut out = Vec::new();
+        dump::json(&mut out, items, &Config::default()).unwrap();
+        out
+    }
+
+    #[test]
+    fn round_trip() {
+        let cmds = vec![
+            Start,
+            UseIntId(true),
+            MakeObj(DObjType::D_NET, DynId(1)),
+            SetType(NetType::NET_TYPE_VERTICES as u32),
+            SetHeaderFlag(0x06),
+            SetFlag(0x1234),
+            SetRotation(Vector { x: 0.1, y: -2.5, z: 1e-3 }),
+            ParamF(6, 0.5),
+            SetColourNum(2),
+            ParamPtr(PtrParam::PARAM_OBJ_VTX, Ptr(3)),
+            MakeValPtr(DynId(4), 1.0, 2, 8.0),
+            Stop,
+        ];
+        let expected: Vec<[u32; 6]> = cmds.iter().map(|c| c.to_struct()).collect();
+        let items = import(&dumped(cmds)[..]).unwrap();
+        let raw: Vec<[u32; 6]> = items.iter().map(|i| *i.raw()).collect();
+        assert_eq!(raw, expected);
+    }
+
+    #[test]
+    fn flag_and_value_names() {
+        let doc: Value = serde_json::from_slice(&dumped(vec![
+            MakeObj(DObjType::D_NET, DynId(1)),
+            SetType(NetType::NET_TYPE_VERTICES as u32),
+            SetHeaderFlag(0x06),
+        ])).unwrap();
+        assert_eq!(doc["commands"][1]["args"][0]["name"], json!("NET_TYPE_VERTICES"));
+        assert_eq!(doc["commands"][2]["args"][0]["names"], json!(["OBJ_INVISIBLE", "OBJ_PICKED"]));
+
+        // names can stand in for the value, as a list or a `|` expression
+        let edited = br#"[
+            {"name": "SetHeaderFlag", "args": [{"type": "flag", "names": ["OBJ_INVISIBLE", "OBJ_PICKED"]}]},
+            {"name": "SetHeaderFlag", "args": ["OBJ_INVISIBLE | OBJ_PICKED"]},
+            {"name": "SetColourNum", "args": ["COLOUR_RED"]}
+        ]"#;
+        let items = import(&edited[..]).unwrap();
+        let cmds: Vec<DynCmd> = items.iter().map(|i| *i.cmd()).collect();
+        assert_eq!(cmds, [SetHeaderFlag(0x06), SetHeaderFlag(0x06), SetColourNum(2)]);
+    }
+
+    #[test]
+    fn bad_command_has_its_index() {
+        let edited = br#"[{"name": "StartList", "args": []}, {"name": "SetNothing", "args": []}]"#;
+        match import(&edited[..]) {
+            Err(Error::BadCmd { index: 1, offset: 24, .. }) => (),
+            other => panic!("expected a bad command error, found {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,48 @@
+//! Decode, encode, and print the binary dynlists used by the SM64 head screen.
+//!
+//! A list is read one command at a time with [`DynListIter`], and each [`DynListItem`]
+//! has both its raw words and its decoded [`DynCmd`]. [`DynCmd::info`] gives the
+//! [`CmdInfo`] metadata used to generate the gas (`asm`) and C (`c89`) macros, and
+//! the emitters in `dump` write a list out in any of the supported text formats.
+//!
+//! A list parsed from C or gas is a [`SourceList`] until `link` or `elf` gives its strings
+//! addresses, a [`Scene`] holds the objects that a list makes, and a [`Mesh`] is a model to
+//! build a list from. The `rom`, `patch`, `batch`, and `split` modules do the work of the
+//! command line tool's `inject`, `patch`, and `batch` commands.
+
+#[macro_use] extern crate bitflags;
+#[macro_use] extern crate serde_json;
+#[macro_use] extern crate serde_derive;
+extern crate byteorder;
+extern crate csv;
+extern crate toml;
+
+pub mod asm;
+pub mod batch;
+pub mod c89;
+pub mod diff;
+pub mod dynlist;
+pub mod dump;
+pub mod elf;
+pub mod error;
+pub mod json;
+pub mod link;
+pub mod yaml;
+pub mod patch;
+pub mod rom;
+pub mod scene;
+pub mod source;
+pub mod split;
+pub mod wavefront;
+pub mod gltf;
+pub mod mesh;
+
+pub use dynlist::{DynListIter, DynListItem, DynCmd, CmdInfo, DynArg, ArgVal, DObjType, PtrParam};
+pub use dynlist::{ObjFlag, HeaderFlag};
+pub use dynlist::{Colour, NetType, FloatParam};
+pub use dynlist::{Limits, Confidence, probe};
+pub use error::Error;
+pub use mesh::Mesh;
+pub use scene::Scene;
+pub use source::SourceList;
+pub use yaml::Document;
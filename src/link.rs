@@ -0,0 +1,147 @@
+//! Lay out the lists from a source file and the strings they use, and fill in the addresses
+//! of strings and symbols once the lists have a place in memory.
+//!
+//! The lists are placed one after another in the command block, in the order of the source,
+//! so that each list's symbol is the address of its first command. Every unique string goes
+//! into a pool after the command block, NUL terminated and aligned to 4 bytes.
+
+use byteorder::{ByteOrder, BE};
+use dynlist::CMD_SIZE;
+use error::Error;
+use source::{SourceList, Target};
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+const STRING_ALIGN: usize = 4;
+
+/// Where each list and string goes, relative to the start of the command block or string pool
+#[derive(Debug, Clone)]
+pub struct Layout {
+    /// Name, offset, and size of each list in the command block
+    pub lists: Vec<(String, u32, u32)>,
+    /// Each string, and its offset in the pool
+    pub strings: Vec<(String, u32)>,
+    pub block_size: u32,
+    pub pool_size: u32,
+}
+
+impl Layout {
+    pub fn new(lists: &[SourceList]) -> Result<Self, Error> {
+        let mut layout = Layout { lists: Vec::new(), strings: Vec::new(), block_size: 0, pool_size: 0 };
+        for list in lists {
+            if layout.list(&list.name).is_some() {
+                return Err(Error::Link { msg: format!("list {} is defined more than once", list.name) });
+            }
+            let size = (list.cmds.len() as u64 * CMD_SIZE) as u32;
+            layout.lists.push((list.name.clone(), layout.block_size, size));
+            layout.block_size += size;
+
+            for target in list.targets() {
+                if let Target::Str(s) = target {
+                    if layout.string(s).is_some() { continue; }
+                    layout.strings.push((s.clone(), layout.pool_size));
+                    let len = s.len() + 1;
+                    layout.pool_size += len.next_multiple_of(STRING_ALIGN) as u32;
+                }
+            }
+        }
+        Ok(layout)
+    }
+    /// Offset of a list in the command block
+    pub fn list(&self, name: &str) -> Option<u32> {
+        self.lists.iter().find(|l| l.0 == name).map(|l| l.1)
+    }
+    /// Offset of a string in the pool
+    pub fn string(&self, s: &str) -> Option<u32> {
+        self.strings.iter().find(|t| t.0 == s).map(|t| t.1)
+    }
+    /// The string pool's bytes
+    pub fn pool(&self) -> Vec<u8> {
+        let mut pool = vec![0; self.pool_size as usize];
+        for (s, offset) in &self.strings {
+            let start = *offset as usize;
+            pool[start..start + s.len()].copy_from_slice(s.as_bytes());
+        }
+        pool
+    }
+}
+
+/// An address in the linked output
+#[derive(Debug, Clone)]
+pub struct Symbol {
+    pub name: String,
+    pub address: u32,
+    pub size: u32,
+    /// The text, if this is a string from the pool
+    pub string: Option<String>,
+}
+
+/// Lists that have been placed at an address, and the string pool after them
+#[derive(Debug, Clone)]
+pub struct Linked {
+    pub data: Vec<u8>,
+    pub symbols: Vec<Symbol>,
+}
+
+/// Lay out `lists` at `base_vram` and fill in every string and symbol address. Symbols that
+/// aren't one of the lists are looked up in `externs`.
+pub fn link(lists: &[SourceList], base_vram: u32, externs: &HashMap<String, u64>) -> Result<Linked, Error> {
+    let layout = Layout::new(lists)?;
+    let pool_start = layout.block_size;
+    let total = u64::from(pool_start) + u64::from(layout.pool_size);
+    if u64::from(base_vram) + total > 1 << 32 {
+        let msg = format!("{:#X} bytes of lists and strings don't fit at {:#010X}", total, base_vram);
+        return Err(Error::Link { msg });
+    }
+
+    let mut undefined = Vec::new();
+    let mut data = Vec::with_capacity(total as usize);
+    for cmd in lists.iter().flat_map(|l| &l.cmds) {
+        let mut raw = cmd.raw;
+        for reloc in &cmd.relocs {
+            let address = match reloc.target {
+                Target::Str(ref s) => layout.string(s).map(|o| u64::from(base_vram + pool_start + o)),
+                Target::Sym(ref name) => layout.list(name).map(|o| u64::from(base_vram + o))
+                    .or_else(|| externs.get(name).cloned()),
+            };
+            raw[reloc.word] = match address {
+                Some(address) if address <= u64::from(u32::MAX) => address as u32,
+                Some(address) => return Err(Error::Link {
+                    msg: format!("symbol {} ({:#X}) is not a 32-bit address", reloc.target, address),
+                }),
+                None => {
+                    if !undefined.contains(&reloc.target) { undefined.push(reloc.target.clone()); }
+                    0
+                },
+            };
+        }
+        let mut bytes = [0; CMD_SIZE as usize];
+        BE::write_u32_into(&raw, &mut bytes);
+        data.extend_from_slice(&bytes);
+    }
+    if !undefined.is_empty() {
+        let names: Vec<String> = undefined.iter().map(|t| t.to_string()).collect();
+        return Err(Error::Link { msg: format!("undefined symbols: {}", names.join(", ")) });
+    }
+    data.extend(layout.pool());
+
+    let mut symbols: Vec<Symbol> = layout.lists.iter()
+        .map(|(name, offset, size)| Symbol { name: name.clone(), address: base_vram + offset, size: *size, string: None })
+        .collect();
+    symbols.extend(layout.strings.iter().map(|(s, offset)| {
+        let address = base_vram + pool_start + offset;
+        Symbol { name: format!("str_{:08X}", address), address, size: s.len() as u32 + 1, string: Some(s.clone()) }
+    }));
+    Ok(Linked { data, symbols })
+}
+
+/// Write `name = 0xADDR;` lines for each symbol, which can be read back as a batch symbol file
+pub fn write_symbols<W: Write>(mut w: W, symbols: &[Symbol]) -> io::Result<()> {
+    for sym in symbols {
+        match sym.string {
+            Some(ref s) => writeln!(w, "{} = {:#010X}; // {:?}", sym.name, sym.address, s)?,
+            None => writeln!(w, "{} = {:#010X}; // {:#X} bytes", sym.name, sym.address, sym.size)?,
+        }
+    }
+    Ok(())
+}
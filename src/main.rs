@@ -1,21 +1,22 @@
 #[macro_use] extern crate failure;
-#[macro_use] extern crate bitflags;
 extern crate structopt;
-extern crate byteorder;
+extern crate dynlistdump;
 use structopt::StructOpt;
 use failure::{Error, ResultExt};
 
-mod asm;
-mod c89;
-mod dynlist;
-mod dump;
-use dynlist::{DynListIter};
+use dynlistdump::{asm, batch, c89, diff, dump, dynlist, elf, json, link, yaml, patch, rom, scene, source, split, wavefront, gltf};
+use dynlistdump::{DynListIter, DynListItem, Limits, Confidence, Mesh};
+use dynlistdump::dump::Format;
 
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::io::{self, BufReader, BufWriter, Write};
-use std::fs::{File, OpenOptions};
+use std::fs::{self, File, OpenOptions};
 use std::num::ParseIntError;
 
+/// Lists that score lower than this when probed get a warning
+const MIN_CONFIDENCE: f32 = 0.6;
+
 /// A tool to help dump a binary SM64 head screen dynlist into a set of asm macros
 #[derive(Debug, StructOpt)]
 enum Opts {
@@ -24,7 +25,7 @@ enum Opts {
     Dump(Dump),
     /// Create the set of gas macros needed for assembling a dumped dynlist
     #[structopt(name="asm")]
-    ASM {
+    Asm {
         #[structopt(parse(from_os_str))]
         /// output file, or stdout if not present
         output: Option<PathBuf>,
@@ -36,6 +37,247 @@ enum Opts {
         /// output file, or stdout if not present
         output: Option<PathBuf>,
     },
+    /// Encode a JSON, YAML, C, or gas dynlist back into a binary dynlist
+    #[structopt(name="build")]
+    Build(Build),
+    /// Export the vertices, faces, and materials made by a dynlist as a Wavefront OBJ
+    #[structopt(name="export-obj")]
+    ExportObj(ListOutput),
+    /// Export the joint hierarchy, meshes, and skin made by a dynlist as a glTF 2.0 file
+    #[structopt(name="export-gltf")]
+    ExportGltf(ListOutput),
+    /// Generate a dynlist that builds the mesh in a Wavefront OBJ or glTF file
+    #[structopt(name="import")]
+    Import(Import),
+    /// Dump every dynlist named in a TOML or CSV manifest
+    #[structopt(name="batch")]
+    Batch(Batch),
+    /// Compare two dynlists command by command
+    #[structopt(name="diff")]
+    Diff(Diff),
+    /// Make an IPS or BPS patch that replaces a list in a ROM with an edited one
+    #[structopt(name="patch")]
+    Patch(Patch),
+    /// Write a text dynlist into a copy of a ROM, and fix up the ROM's checksums
+    #[structopt(name="inject")]
+    Inject(Inject),
+    /// Assemble the lists in a C or gas source into a binary, with their strings, at an address
+    #[structopt(name="assemble")]
+    Assemble(Assemble),
+}
+
+/// A binary dynlist and where to write whatever is produced from it
+#[derive(Debug, StructOpt)]
+struct ListOutput {
+    /// input binary file to read dynlist from
+    #[structopt(parse(from_os_str))]
+    input: PathBuf,
+    /// offset to start of dynlist
+    offset: Option<String>,
+    /// output file, or stdout if not present
+    #[structopt(parse(from_os_str))]
+    output: Option<PathBuf>,
+    #[structopt(flatten)]
+    guards: Guards,
+}
+
+/// Dump many lists from the same binary
+#[derive(Debug, StructOpt)]
+struct Batch {
+    /// manifest of lists to dump (.toml or .csv)
+    #[structopt(parse(from_os_str))]
+    manifest: PathBuf,
+    /// input binary file to read the lists from, instead of the manifest's rom
+    #[structopt(parse(from_os_str))]
+    input: Option<PathBuf>,
+    /// file of `name = 0xADDR;` symbols, instead of the manifest's symbols
+    #[structopt(short = "s", long = "symbols", parse(from_os_str))]
+    symbols: Option<PathBuf>,
+    /// address that symbols are relative to, instead of the manifest's base_vram
+    #[structopt(long = "base-vram")]
+    base_vram: Option<String>,
+    /// file offset of base_vram, instead of the manifest's base_offset
+    #[structopt(long = "base-offset")]
+    base_offset: Option<String>,
+    /// write every list into this directory as a decomp-style split, ignoring the manifest's outputs
+    #[structopt(long = "split", parse(from_os_str))]
+    split: Option<PathBuf>,
+    #[structopt(flatten)]
+    guards: Guards,
+}
+
+/// Two lists to compare, from the same or different files
+#[derive(Debug, StructOpt)]
+struct Diff {
+    /// binary file with the original list
+    #[structopt(parse(from_os_str))]
+    old: PathBuf,
+    /// offset to start of the original list
+    old_offset: String,
+    /// binary file with the changed list
+    #[structopt(parse(from_os_str))]
+    new: PathBuf,
+    /// offset to start of the changed list, if it's different from the original's
+    new_offset: Option<String>,
+    /// number of unchanged commands to show around each change
+    #[structopt(short = "U", long = "context", default_value = "3")]
+    context: usize,
+    /// compare the objects that the lists make, instead of their commands
+    #[structopt(short = "o", long = "objects")]
+    objects: bool,
+    #[structopt(flatten)]
+    guards: Guards,
+}
+
+/// An edited list to put in place of one in a ROM
+#[derive(Debug, StructOpt)]
+struct Patch {
+    /// original big-endian (.z64) ROM
+    #[structopt(parse(from_os_str))]
+    rom: PathBuf,
+    /// offset of the list to replace
+    offset: String,
+    /// edited JSON (.json), YAML (.yaml, .yml), C (.c), or gas (.s) dynlist
+    #[structopt(parse(from_os_str))]
+    list: PathBuf,
+    /// which list to read from a C or gas source, instead of the first
+    #[structopt(long = "name")]
+    name: Option<String>,
+    /// patch file to write, or stdout if neither this nor --rom-out is given
+    #[structopt(short = "o", long = "output", parse(from_os_str))]
+    output: Option<PathBuf>,
+    /// patch format: ips or bps (defaults to the output's extension, then ips)
+    #[structopt(short = "f", long = "format")]
+    format: Option<patch::Format>,
+    /// also write the patched ROM, with its header checksums updated
+    #[structopt(long = "rom-out", parse(from_os_str))]
+    rom_out: Option<PathBuf>,
+    /// write the list at this ROM offset instead of over the original, which it may not fit in
+    #[structopt(long = "relocate")]
+    relocate: Option<String>,
+    #[structopt(flatten)]
+    guards: Guards,
+}
+
+/// A list to write into a ROM, and where to put it
+#[derive(Debug, StructOpt)]
+struct Inject {
+    /// original big-endian (.z64) ROM
+    #[structopt(parse(from_os_str))]
+    rom: PathBuf,
+    /// JSON (.json), YAML (.yaml, .yml), C (.c), or gas (.s) dynlist to write into the ROM
+    #[structopt(parse(from_os_str))]
+    list: PathBuf,
+    /// which list to read from a C or gas source, instead of the first
+    #[structopt(long = "name")]
+    name: Option<String>,
+    /// where to write the modified copy of the ROM
+    #[structopt(parse(from_os_str))]
+    output: PathBuf,
+    /// ROM offset to write the list at
+    #[structopt(long = "offset", raw(required_unless = "\"free\"", conflicts_with = "\"free\""))]
+    offset: Option<String>,
+    /// range of ROM offsets (START-END) to find unused 0x00 or 0xFF bytes for the list in
+    #[structopt(long = "free")]
+    free: Option<String>,
+    /// ROM offset of a pointer to change to the list's address (can be given more than once)
+    #[structopt(long = "repoint")]
+    repoint: Vec<String>,
+    /// ROM offset of a JumpToList command to change to the list's address (can be given more than once)
+    #[structopt(long = "repoint-jump")]
+    repoint_jump: Vec<String>,
+    /// address of the ROM offset given by --base-offset, for working out the list's address
+    #[structopt(long = "base-vram", default_value = "0x80000000")]
+    base_vram: String,
+    /// ROM offset that is loaded at --base-vram
+    #[structopt(long = "base-offset", default_value = "0")]
+    base_offset: String,
+}
+
+/// A source file to assemble, and where it will be loaded
+#[derive(Debug, StructOpt)]
+struct Assemble {
+    /// C (.c) or gas (.s) dynlist source
+    #[structopt(parse(from_os_str))]
+    input: PathBuf,
+    /// binary file to write the lists and their strings to, or a relocatable MIPS ELF object if it ends in .o
+    #[structopt(parse(from_os_str))]
+    output: PathBuf,
+    /// address that the output will be loaded at
+    #[structopt(long = "base-vram", default_value = "0x80000000")]
+    base_vram: String,
+    /// file of `name = 0xADDR;` addresses for symbols that aren't lists in the source
+    #[structopt(short = "s", long = "symbols", parse(from_os_str))]
+    symbols: Option<PathBuf>,
+    /// file to write the address of each list and string to, or stdout if not present
+    #[structopt(short = "m", long = "map", parse(from_os_str))]
+    map: Option<PathBuf>,
+}
+
+/// Limits for reading a list that might not have a `StopList`
+#[derive(Debug, StructOpt)]
+struct Guards {
+    /// give up after reading this many commands
+    #[structopt(long = "max-cmds", default_value = "20000")]
+    max_cmds: usize,
+    /// give up after reading this many bytes
+    #[structopt(long = "max-bytes")]
+    max_bytes: Option<String>,
+    /// give up at the first unknown command
+    #[structopt(long = "stop-at-unknown")]
+    stop_at_unknown: bool,
+    /// keep reading past a StartList, instead of treating it as the start of the next list
+    #[structopt(long = "ignore-start")]
+    ignore_start: bool,
+}
+
+impl Guards {
+    fn limits(&self) -> Result<Limits, Error> {
+        let max_bytes = match self.max_bytes {
+            Some(ref n) => Some(hex_or_dec(n).context("parsing max bytes into integer")?),
+            None => None,
+        };
+        Ok(Limits {
+            max_cmds: Some(self.max_cmds),
+            max_bytes,
+            stop_at_unknown: self.stop_at_unknown,
+            stop_at_start: !self.ignore_start,
+        })
+    }
+}
+
+/// Encode a text dynlist into binary, or convert it to another text format
+#[derive(Debug, StructOpt)]
+struct Build {
+    /// input JSON (.json), YAML (.yaml, .yml), C (.c), or gas (.s) dynlist
+    #[structopt(parse(from_os_str))]
+    input: PathBuf,
+    /// output file, or stdout if not present
+    #[structopt(parse(from_os_str))]
+    output: Option<PathBuf>,
+    /// write the list in this text format instead of binary: gas, c, json, yaml, or listing
+    #[structopt(short = "f", long = "format")]
+    format: Option<Format>,
+    /// which list to read from a C or gas source, instead of the first
+    #[structopt(long = "name")]
+    name: Option<String>,
+}
+
+/// Generate a dynlist from a model file
+#[derive(Debug, StructOpt)]
+struct Import {
+    /// input Wavefront OBJ (.obj), glTF (.gltf), or binary glTF (.glb) model
+    #[structopt(parse(from_os_str))]
+    input: PathBuf,
+    /// output file, or stdout if not present
+    #[structopt(parse(from_os_str))]
+    output: Option<PathBuf>,
+    /// write the list in this text format instead of binary: gas, c, json, yaml, or listing
+    #[structopt(short = "f", long = "format")]
+    format: Option<Format>,
+    /// first integer id to use for the generated groups and shape
+    #[structopt(short = "b", long = "base-id", default_value = "1")]
+    base_id: String,
 }
 
 /// Dump a binary dynlist into a list of gas or C macros
@@ -49,15 +291,47 @@ struct Dump {
     /// output file, or stdout if not present
     #[structopt(parse(from_os_str))]
     output: Option<PathBuf>,
-    /// print out the C macros instead of gas
-    #[structopt(short = "c", long = "c-macros", raw(conflicts_with_all = "&[\"info\", \"raw\"]"))]
+    /// print out the C macros instead of gas (the same as --format c)
+    #[structopt(short = "c", long = "c-macros", raw(conflicts_with = "\"format\""))]
     c: bool,
+    /// output format for the dumped list: gas, c, json, yaml, or listing
+    #[structopt(short = "f", long = "format")]
+    format: Option<Format>,
     /// print out the raw values of cmd as a comment
-    #[structopt(short = "r", long = "raw-values", raw(conflicts_with_all = "&[\"info\", \"c\"]"))]
+    #[structopt(short = "r", long = "raw-values")]
     raw: bool,
+    /// comment each command with its file offset
+    #[structopt(long = "offsets")]
+    offsets: bool,
+    /// comment each command with the object types it acts on
+    #[structopt(long = "obj-types")]
+    objs: bool,
+    /// comment each command with a description of what it does
+    #[structopt(long = "desc")]
+    desc: bool,
+    /// comment each command with the object and group it acts on, and warn when the object's type doesn't fit
+    #[structopt(long = "annotate")]
+    annotate: bool,
+    /// indent groups and net subgroups, and put a blank line before each new object (gas and c)
+    #[structopt(long = "pretty")]
+    pretty: bool,
+    /// with --pretty, also indent the setters that follow a new object under it
+    #[structopt(long = "group-setters", requires = "pretty")]
+    group_setters: bool,
+    /// address that the list is loaded at, for the listing's vram column
+    #[structopt(long = "vram")]
+    vram: Option<String>,
     /// print info about a list, rather than dumping the bytes
-    #[structopt(short = "i", long = "info", raw(conflicts_with_all = "&[\"raw\", \"c\"]"))]
+    #[structopt(short = "i", long = "info")]
     info: bool,
+    /// print the list info as JSON (requires --info)
+    #[structopt(short = "j", long = "json", raw(requires = "\"info\""))]
+    json: bool,
+    /// write the list, shared headers, and build includes into this directory
+    #[structopt(long = "split", parse(from_os_str), raw(conflicts_with_all = "&[\"info\", \"output\"]"))]
+    split: Option<PathBuf>,
+    #[structopt(flatten)]
+    guards: Guards,
 }
 
 fn main() {
@@ -68,35 +342,427 @@ fn main() {
         for c in e.iter_causes() {
             eprintln!("caused by: {}", c);
         }
+        if let Some(e) = e.iter_chain().find_map(|c| c.downcast_ref::<dynlistdump::Error>()) {
+            print_diagnostic(e);
+        }
         ::std::process::exit(1);
     }
 }
 
+/// Point out the command that a decoding error happened at, and the raw word that caused it
+fn print_diagnostic(e: &dynlistdump::Error) {
+    if let (Some(offset), Some(index), Some((raw, word))) = (e.offset(), e.index(), e.raw()) {
+        let words: Vec<String> = raw.iter().map(|w| format!("{:08x}", w)).collect();
+        eprintln!("  --> command {} at {:#X}", index, offset);
+        eprintln!("   |");
+        eprintln!("   | {}", words.join(" "));
+        eprintln!("   | {}{}", " ".repeat(word * 9), "^".repeat(8));
+    }
+}
+
 fn run(opts: Opts) -> Result<(),Error> {
     match opts {
         Opts::Dump(dump)  => dump_dynlist(dump),
-        Opts::ASM{output} => produce_asm_macros(output),
+        Opts::Asm{output} => produce_asm_macros(output),
         Opts::C{output}   => produce_c_header(output),
+        Opts::Build(build) => build_dynlist(build),
+        Opts::ExportObj(opts) => export_obj(opts),
+        Opts::ExportGltf(opts) => export_gltf(opts),
+        Opts::Import(opts) => import_mesh(opts),
+        Opts::Batch(opts) => batch_dump(opts),
+        Opts::Diff(opts) => diff_dynlists(opts),
+        Opts::Patch(opts) => patch_rom(opts),
+        Opts::Inject(opts) => inject_list(opts),
+        Opts::Assemble(opts) => assemble_source(opts),
     }
 }
 
-fn dump_dynlist(opts: Dump) -> Result<(), Error> {
-    let f = File::open(opts.input).context("opening input binary file")?;
-    let rdr = BufReader::new(f);
-    let offset = opts.offset.as_ref()
+/// Open a binary file and start reading a dynlist at `offset` (or the start of the file).
+/// Warns if the start of the list doesn't look like a dynlist.
+fn open_dynlist(input: &Path, offset: Option<&String>, guards: &Guards) -> Result<(DynListIter<BufReader<File>>, u64), Error> {
+    let offset = offset
         .map(hex_or_dec)
         .unwrap_or(Ok(0))
         .context("parsing offset into integer")?;
-    let dynlist = DynListIter::from_reader(rdr, offset).context("generating dynlist iterator")?;
+    let f = File::open(input).context("opening input binary file")?;
+    let dynlist = DynListIter::from_reader(BufReader::new(f), offset)
+        .context("generating dynlist iterator")?
+        .limits(guards.limits()?);
+
+    let mut f = File::open(input).context("opening input binary file")?;
+    let confidence = dynlist::probe(&mut f, offset).context("checking start of dynlist")?;
+    warn_if_unlikely(offset, &confidence);
+    Ok((dynlist, offset))
+}
+
+/// Print a warning if the start of a list doesn't look like a dynlist
+fn warn_if_unlikely(offset: u64, confidence: &Confidence) {
+    if confidence.score < MIN_CONFIDENCE {
+        eprintln!("warning: {:#X} is probably not the start of a dynlist ({:.0}% confidence)",
+            offset, confidence.score * 100.0);
+        for reason in &confidence.reasons {
+            eprintln!("  - {}", reason);
+        }
+    }
+}
+
+/// Dump every list in a manifest out of a single open binary, and summarize how it went
+fn batch_dump(opts: Batch) -> Result<(), Error> {
+    let mut manifest = batch::Manifest::load(&opts.manifest)?;
+    if let Some(ref n) = opts.base_vram {
+        manifest.base_vram = Some(hex_or_dec(n).context("parsing base vram into integer")?);
+    }
+    if let Some(ref n) = opts.base_offset {
+        manifest.base_offset = Some(hex_or_dec(n).context("parsing base offset into integer")?);
+    }
+    let rom = match (opts.input, manifest.rom.as_ref()) {
+        (Some(input), _) => input,
+        (None, Some(rom)) => manifest.path(rom),
+        (None, None) => bail!("no input binary given, and the manifest has no rom"),
+    };
+    let symbols = match (opts.symbols, manifest.symbols.as_ref()) {
+        (Some(path), _) => batch::load_symbols(&path)?,
+        (None, Some(path)) => batch::load_symbols(&manifest.path(path))?,
+        (None, None) => HashMap::new(),
+    };
+    let rdr = BufReader::new(File::open(&rom).context("opening input binary file")?);
+
+    let outcomes = batch::dump_all(&manifest, rdr, &symbols, opts.guards.limits()?, opts.split.as_deref())?;
+    let failed = outcomes.iter().filter(|o| o.result.is_err()).count();
+    for outcome in &outcomes {
+        if let Some((offset, ref confidence)) = outcome.probe {
+            warn_if_unlikely(offset, confidence);
+        }
+        match outcome.result {
+            Ok((ref job, count)) => println!("ok    {:<24} {:#010X} {:>6} cmds -> {}",
+                job.name, job.offset, count, job.output.display()),
+            Err(ref e) => println!("FAIL  {:<24} {}", outcome.name, e),
+        }
+    }
+    let total = manifest.lists.len();
+    println!("{} of {} lists dumped, {} failed", total - failed, total, failed);
+    if failed > 0 { bail!("{} of {} lists failed", failed, total); }
+    Ok(())
+}
+
+fn dump_dynlist(opts: Dump) -> Result<(), Error> {
+    let format = if opts.c { Format::C } else { opts.format.unwrap_or(Format::Gas) };
+    let notes = dump::Notes { raw: opts.raw, offsets: opts.offsets, objs: opts.objs, desc: opts.desc,
+        annotate: opts.annotate };
+    let style = match (opts.pretty, opts.group_setters) {
+        (_, true)     => dump::Style::Grouped,
+        (true, false) => dump::Style::Pretty,
+        _             => dump::Style::Flat,
+    };
+    if let Some(ref dir) = opts.split {
+        let offset = opts.offset.as_ref()
+            .map(hex_or_dec)
+            .unwrap_or(Ok(0))
+            .context("parsing offset into integer")?;
+        let mut rdr = BufReader::new(File::open(&opts.input).context("opening input binary file")?);
+        let confidence = dynlist::probe(&mut rdr, offset).context("checking start of dynlist")?;
+        warn_if_unlikely(offset, &confidence);
+        let cfg = dump::Config { notes, style, ..dump::Config::new(offset) };
+        let list = split::write_list(&mut rdr, dir, &cfg, format, opts.guards.limits()?)?;
+        split::write_support_files(dir, &[list]).context("writing split headers")?;
+        return Ok(());
+    }
+
+    let (dynlist, offset) = open_dynlist(&opts.input, opts.offset.as_ref(), &opts.guards)?;
+    let wtr = get_file_or_stdout(opts.output).context("opening output file")?;
+    let vram = match opts.vram {
+        Some(ref n) => Some(hex_or_dec(n).context("parsing vram into integer")?),
+        None => None,
+    };
+    let cfg = dump::Config { vram, notes, style, ..dump::Config::new(offset) };
+
+    if opts.info {
+        dump::info(wtr, dynlist, &cfg, opts.json)?;
+    } else {
+        dump::write(format, wtr, dynlist, &cfg)?;
+    }
+    Ok(())
+}
+
+/// Print a command-aligned diff of two lists, or a diff of the objects they make
+fn diff_dynlists(opts: Diff) -> Result<(), Error> {
+    let new_offset = opts.new_offset.as_ref().unwrap_or(&opts.old_offset);
+    let read = |input: &Path, offset: &String| -> Result<(Vec<DynListItem>, u64), Error> {
+        let (dynlist, address) = open_dynlist(input, Some(offset), &opts.guards)?;
+        let items = dynlist.collect::<Result<_, _>>()
+            .with_context(|_| format!("reading list at {} in {}", offset, input.display()))?;
+        Ok((items, address))
+    };
+    let (old, old_address) = read(&opts.old, &opts.old_offset)?;
+    let (new, new_address) = read(&opts.new, new_offset)?;
+    let (old_label, new_label) = (opts.old.display().to_string(), opts.new.display().to_string());
+
+    let stdout = io::stdout();
+    let summary = if opts.objects {
+        let interpret = |items: Vec<DynListItem>, address: u64, label: &str| -> Result<scene::Scene, Error> {
+            let scene = scene::Scene::from_items(items.into_iter().map(Ok), address)?;
+            for warning in &scene.warnings {
+                eprintln!("warning: {}: {}", label, warning);
+            }
+            Ok(scene)
+        };
+        let old = interpret(old, old_address, &old_label)?;
+        let new = interpret(new, new_address, &new_label)?;
+        diff::write_scenes(stdout.lock(), (&old_label, &old), (&new_label, &new))?
+    } else {
+        let list = |items: Vec<DynListItem>, address: u64, label: String| diff::List {
+            label, address, cmds: items.iter().map(|i| *i.cmd()).collect(),
+        };
+        let old = list(old, old_address, old_label);
+        let new = list(new, new_address, new_label);
+        diff::write(stdout.lock(), &old, &new, opts.context)?
+    };
+    let what = if opts.objects { "objects" } else { "cmds" };
+    if summary.is_same() {
+        println!("lists are the same ({} {})", summary.same, what);
+    } else {
+        println!("{} changed, {} added, {} removed, {} unchanged",
+            summary.changed, summary.added, summary.removed, summary.same);
+    }
+    Ok(())
+}
+
+/// Encode a text dynlist into the binary format used by the game
+fn build_dynlist(opts: Build) -> Result<(), Error> {
+    // strings and symbols only get addresses when linked, so binary output can't have them
+    let doc = read_text_list(&opts.input, opts.name.as_ref(), opts.format.is_some())?;
+    let wtr = get_file_or_stdout(opts.output).context("opening output file")?;
+
+    match opts.format {
+        Some(Format::Yaml) => Ok(doc.write(wtr)?),
+        format => write_items(wtr, doc.into_items(), format),
+    }
+}
+
+/// Read a JSON, YAML, C, or gas text list, picking out the list called `name` from a C or gas
+/// source. If `placeholders` is set, any strings or symbols in a source list are given
+/// placeholder values; otherwise they're an error.
+fn read_text_list(input: &Path, name: Option<&String>, placeholders: bool) -> Result<yaml::Document, Error> {
+    let ext = input.extension().and_then(|ext| ext.to_str()).unwrap_or("");
+    if ext == "c" || ext == "s" {
+        let list = read_source_list(input, name)?;
+        let targets = list.targets();
+        if !targets.is_empty() {
+            if !placeholders {
+                let names: Vec<String> = targets.iter().map(|t| t.to_string()).collect();
+                bail!("{} refers to strings or symbols that need to be linked with `assemble`: {}", list.name, names.join(", "));
+            }
+            for (i, target) in targets.iter().enumerate() {
+                eprintln!("warning: {} is written as placeholder {:#010X}", target, source::PLACEHOLDER_BASE + i as u32);
+            }
+        }
+        return Ok(yaml::Document::from_items(list.items(), &list.name));
+    }
+
+    let f = File::open(input).context("opening input dynlist")?;
+    let rdr = BufReader::new(f);
+    if ext == "yaml" || ext == "yml" {
+        Ok(yaml::Document::parse(rdr).context("parsing YAML dynlist")?)
+    } else {
+        Ok(yaml::Document::from_items(json::import(rdr)?, &dump::Config::default().label()))
+    }
+}
+
+/// Parse a C (.c) or gas (.s) source, and take the list called `name` from it, or else the first list
+fn read_source_list(input: &Path, name: Option<&String>) -> Result<source::SourceList, Error> {
+    let lists = source::read(input).with_context(|_| format!("parsing {}", input.display()))?;
+    let found = match name {
+        Some(name) => lists.into_iter().find(|l| &l.name == name),
+        None => {
+            if lists.len() > 1 {
+                eprintln!("warning: {} has {} lists; using the first, {}", input.display(), lists.len(), lists[0].name);
+            }
+            lists.into_iter().next()
+        },
+    };
+    match (found, name) {
+        (Some(list), _) => Ok(list),
+        (None, Some(name)) => bail!("no list named {} in {}", name, input.display()),
+        (None, None) => bail!("no dynlists in {}", input.display()),
+    }
+}
+
+/// Encode an edited list, put it in a copy of a ROM, and write a patch and/or the patched ROM
+fn patch_rom(opts: Patch) -> Result<(), Error> {
+    let offset = hex_or_dec(&opts.offset).context("parsing offset into integer")?;
+    let relocate = match opts.relocate {
+        Some(ref n) => Some(hex_or_dec(n).context("parsing relocation offset into integer")?),
+        None => None,
+    };
+    let original = fs::read(&opts.rom).context("reading ROM")?;
+    let bytes = encode_text_list(&opts.list, opts.name.as_ref())?;
+    let patched = patch::replace_list(&original, offset, &bytes, relocate, opts.guards.limits()?)?;
+    if !patched.crc_updated {
+        eprintln!("warning: {} isn't a big-endian N64 ROM, so its checksums weren't updated", opts.rom.display());
+    }
+    eprintln!("{:#x} byte list written at {:#X} (the original was {:#x} bytes)", bytes.len(), patched.at, patched.slot);
+    if patched.at != offset {
+        eprintln!("note: anything that pointed to the list at {:#X} needs to point to {:#X} now", offset, patched.at);
+    }
+
+    if let Some(ref path) = opts.rom_out {
+        fs::write(path, &patched.rom).context("writing patched ROM")?;
+    }
+    if opts.output.is_some() || opts.rom_out.is_none() {
+        let format = opts.format
+            .or_else(|| opts.output.as_ref()
+                .and_then(|o| o.extension())
+                .and_then(|e| e.to_string_lossy().parse().ok()))
+            .unwrap_or(patch::Format::Ips);
+        let wtr = get_file_or_stdout(opts.output).context("opening output patch")?;
+        patch::write(format, wtr, &original, &patched.rom).context("writing patch")?;
+    }
+    Ok(())
+}
+
+/// Write a list into a copy of a ROM, point other code and lists at it, and fix the checksums
+fn inject_list(opts: Inject) -> Result<(), Error> {
+    let place = match (&opts.offset, &opts.free) {
+        (Some(n), _) => rom::Place::At(hex_or_dec(n).context("parsing offset into integer")? as usize),
+        (None, Some(range)) => {
+            let mut ends = range.splitn(2, '-');
+            let start = hex_or_dec(ends.next().unwrap_or_default()).context("parsing start of free range")?;
+            let end = match ends.next() {
+                Some(end) => Some(hex_or_dec(end).context("parsing end of free range")? as usize),
+                None => None,
+            };
+            rom::Place::Free { start: start as usize, end }
+        },
+        (None, None) => bail!("give an --offset or a --free range to write the list at"),
+    };
+    let base_vram = hex_or_dec(&opts.base_vram).context("parsing base vram into integer")?;
+    let base_offset = hex_or_dec(&opts.base_offset).context("parsing base offset into integer")?;
+    let mut pointers = Vec::new();
+    for loc in &opts.repoint {
+        pointers.push(rom::Pointer::Word(hex_or_dec(loc).context("parsing pointer offset into integer")? as usize));
+    }
+    for loc in &opts.repoint_jump {
+        pointers.push(rom::Pointer::Jump(hex_or_dec(loc).context("parsing jump offset into integer")? as usize));
+    }
+
+    let mut rom_data = fs::read(&opts.rom).context("reading ROM")?;
+    let bytes = encode_text_list(&opts.list, opts.name.as_ref())?;
+    let injected = rom::inject(&mut rom_data, &bytes, place, base_vram, base_offset, &pointers)?;
+    for loc in &injected.pointers {
+        eprintln!("pointer at {:#X} -> {:#010X}", loc, injected.address);
+    }
+    if !injected.crc_updated {
+        eprintln!("warning: {} isn't a big-endian N64 ROM, so its checksums weren't updated", opts.rom.display());
+    }
+    fs::write(&opts.output, &rom_data).context("writing output ROM")?;
+    eprintln!("{:#x} byte list written at {:#X} ({:#010X})", bytes.len(), injected.at, injected.address);
+    Ok(())
+}
+
+/// Read a text list whose strings and symbols are all given as numbers, and encode it into binary
+fn encode_text_list(input: &Path, name: Option<&String>) -> Result<Vec<u8>, Error> {
+    Ok(read_text_list(input, name, false)?.into_items().iter()
+        .flat_map(|item| item.to_bytes().to_vec())
+        .collect())
+}
+
+/// Lay out every list in a source file and its strings at an address, and write the binary and its symbols
+fn assemble_source(opts: Assemble) -> Result<(), Error> {
+    match opts.input.extension().and_then(|ext| ext.to_str()) {
+        Some("c") | Some("s") => {},
+        _ => bail!("{} isn't a C (.c) or gas (.s) source", opts.input.display()),
+    }
+    let lists = source::read(&opts.input).with_context(|_| format!("parsing {}", opts.input.display()))?;
+    if lists.is_empty() { bail!("no dynlists in {}", opts.input.display()); }
+
+    if opts.output.extension().is_some_and(|ext| ext == "o") {
+        // the object's symbols are left for the build's linker to place and resolve
+        if opts.symbols.is_some() || opts.map.is_some() {
+            bail!("--symbols and --map don't apply to an ELF object, which is placed by the linker");
+        }
+        let wtr = BufWriter::new(File::create(&opts.output).context("creating output object")?);
+        elf::write(wtr, &lists)?;
+        eprintln!("{} lists written to {}", lists.len(), opts.output.display());
+        return Ok(());
+    }
+    let base_vram = hex_or_dec(&opts.base_vram).context("parsing base vram into integer")?;
+    if base_vram > u64::from(u32::MAX) { bail!("base vram {:#X} is not a 32-bit address", base_vram); }
+    let externs = match opts.symbols {
+        Some(ref path) => batch::load_symbols(path)?,
+        None => HashMap::new(),
+    };
+    let linked = link::link(&lists, base_vram as u32, &externs)?;
+    fs::write(&opts.output, &linked.data).context("writing output binary")?;
+    let wtr = get_file_or_stdout(opts.map).context("opening symbol map")?;
+    link::write_symbols(wtr, &linked.symbols).context("writing symbol map")?;
+    eprintln!("{} lists and {} strings assembled into {:#x} bytes",
+        lists.len(), linked.symbols.len() - lists.len(), linked.data.len());
+    Ok(())
+}
+
+/// Read a mesh from a model file, and write out a dynlist that builds it
+fn import_mesh(opts: Import) -> Result<(), Error> {
+    let base_id = hex_or_dec(&opts.base_id).context("parsing base id into integer")?;
+    if base_id > u64::from(u32::MAX - 3) { bail!("base id {:#x} is too large", base_id); }
+    let mesh = Mesh::load(&opts.input)?;
+    eprintln!("{} vertices, {} faces, {} materials",
+        mesh.vertices.len(), mesh.faces.len(), mesh.materials.len());
+
     let wtr = get_file_or_stdout(opts.output).context("opening output file")?;
+    write_items(wtr, mesh.to_items(base_id as u32), opts.format)
+}
+
+/// Write a list of commands as binary, or in a text format
+fn write_items<W: Write>(mut wtr: W, items: Vec<DynListItem>, format: Option<Format>) -> Result<(), Error> {
+    let cfg = dump::Config::default();
+    let dynlist = items.into_iter().map(Ok);
+    match format {
+        None => {
+            for cmd in dynlist {
+                wtr.write_all(&cmd?.to_bytes())?;
+            }
+        },
+        Some(format) => dump::write(format, wtr, dynlist, &cfg)?,
+    }
+    Ok(())
+}
 
-    match (opts.info, opts.raw, opts.c) {
-        (true, false, false)  => dump::info(wtr, dynlist, offset),
-        (false, true, false)  => dump::raw(wtr, dynlist),
-        (false, false, true)  => dump::c(wtr, dynlist, offset),
-        (false, false, false) => dump::gas(wtr, dynlist, offset),
-        _ => bail!("Illegal combination of dump flags"),
+/// Interpret a dynlist into its objects, and print any problems that came up
+fn interpret_dynlist(opts: &ListOutput) -> Result<(scene::Scene, u64), Error> {
+    let (dynlist, offset) = open_dynlist(&opts.input, opts.offset.as_ref(), &opts.guards)?;
+    let scene = scene::Scene::from_items(dynlist, offset)?;
+    for warning in &scene.warnings {
+        eprintln!("warning: {}", warning);
     }
+    Ok((scene, offset))
+}
+
+/// Interpret a dynlist, and write out an OBJ (and MTL, if there's an output file) of its mesh
+fn export_obj(opts: ListOutput) -> Result<(), Error> {
+    let (scene, offset) = interpret_dynlist(&opts)?;
+    let name = format!("list_{:08X}", offset);
+
+    let mtl_path = opts.output.as_ref().map(|o| o.with_extension("mtl"));
+    if let Some(ref path) = mtl_path {
+        let wtr = get_file_or_stdout(Some(path.clone())).context("opening output mtl file")?;
+        wavefront::write_mtl(wtr, &scene, &name)?;
+    }
+    let mtllib = mtl_path.as_ref()
+        .and_then(|p| p.file_name())
+        .map(|f| f.to_string_lossy().into_owned());
+    let wtr = get_file_or_stdout(opts.output).context("opening output file")?;
+    wavefront::write_obj(wtr, &scene, &name, mtllib.as_deref())?;
+    Ok(())
+}
+
+/// Interpret a dynlist, and write out a self-contained glTF of its rig and meshes
+fn export_gltf(opts: ListOutput) -> Result<(), Error> {
+    let (scene, offset) = interpret_dynlist(&opts)?;
+    let wtr = get_file_or_stdout(opts.output).context("opening output file")?;
+    for warning in gltf::write(wtr, &scene, &format!("list_{:08X}", offset))? {
+        eprintln!("warning: {}", warning);
+    }
+    Ok(())
 }
 
 /// Create a set of GNU AS macros for assemble a dynlist to bytecode
@@ -117,16 +783,15 @@ fn hex_or_dec<S>(n: S) -> Result<u64, ParseIntError>
     where S: AsRef<str>
 {
     let n: &str = n.as_ref();
-    let op = &n[0..2];
     
-    if op == "0x" || op == "0X" { 
-        u64::from_str_radix(&n[2..], 16)
+    if let Some(hex) = n.strip_prefix("0x").or_else(|| n.strip_prefix("0X")) { 
+        u64::from_str_radix(hex, 16)
     } else { 
-        u64::from_str_radix(n, 10)
+        n.parse()
     }
 }
 
-fn get_file_or_stdout(out: Option<PathBuf>) -> Result<BufWriter<Box<Write>>, io::Error> {
+fn get_file_or_stdout(out: Option<PathBuf>) -> Result<BufWriter<Box<dyn Write>>, io::Error> {
     Ok(BufWriter::new(
         if let Some(f) = out {
             let f = OpenOptions::new()
@@ -134,9 +799,9 @@ fn get_file_or_stdout(out: Option<PathBuf>) -> Result<BufWriter<Box<Write>>, io:
                 .create(true)
                 .truncate(true)
                 .open(f)?;
-            Box::new(f) as Box<Write>
+            Box::new(f) as Box<dyn Write>
         } else {
-            Box::new(io::stdout()) as Box<Write>
+            Box::new(io::stdout()) as Box<dyn Write>
         }
     ))
 }
@@ -0,0 +1,148 @@
+//! A plain triangle mesh read from a model file, and the dynlist that builds it:
+//! ```text
+//! StartList
+//! UseIntId TRUE
+//! StartGroup mtls         ; a D_MATERIAL for each material, numbered with SetId
+//! StartGroup vtx          ; a MakeVertex for each vertex, with its normal and ST
+//! StartGroup faces        ; a D_FACE for each triangle, with its material and vertex indices
+//! UseObj faces            ; resolve the faces' material numbers and vertex indices
+//! MakeDynObj D_SHAPE      ; a shape made out of the three groups
+//! StopList
+//! ```
+
+use dynlist::{DynListItem, DynId, DObjType, Ptr, PtrParam, Vector};
+use dynlist::DynCmd::*;
+use error::Error;
+use gltf;
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::Path;
+use wavefront;
+
+#[derive(Debug, Clone)]
+pub struct Vertex {
+    pub pos: Vector,
+    pub normal: Option<Vector>,
+    pub st: Option<(f32, f32)>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Material {
+    pub name: String,
+    pub ambient: Option<Vector>,
+    pub diffuse: Option<Vector>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Face {
+    pub vertices: [u32; 3],
+    /// Index into the mesh's materials
+    pub material: Option<usize>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Mesh {
+    pub vertices: Vec<Vertex>,
+    pub faces: Vec<Face>,
+    pub materials: Vec<Material>,
+}
+
+/// Ids of the objects made for a mesh, counting up from a base id
+#[derive(Debug, Copy, Clone)]
+struct MeshIds {
+    mtls: DynId,
+    vtx: DynId,
+    faces: DynId,
+    shape: DynId,
+}
+
+impl Mesh {
+    /// Load a Wavefront OBJ (`.obj`), glTF (`.gltf`), or binary glTF (`.glb`) model. Material
+    /// libraries and buffers are found relative to the model.
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let file_err = |err| Error::File { path: path.display().to_string(), err };
+        let dir = path.parent().unwrap_or_else(|| Path::new("."));
+        let ext = path.extension()
+            .map(|e| e.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        match ext.as_str() {
+            "obj" => wavefront::read_obj(BufReader::new(File::open(path).map_err(file_err)?), dir),
+            "gltf" | "glb" => gltf::read(&fs::read(path).map_err(file_err)?, dir),
+            _ => Err(Error::Syntax { what: "model", line: None,
+                msg: format!("unknown model type \"{}\" (expected .obj, .gltf, or .glb)", ext) }),
+        }
+    }
+
+    /// Add a polygon as a fan of triangles around its first vertex
+    pub fn add_polygon(&mut self, vertices: &[u32], material: Option<usize>) {
+        for pair in vertices.windows(2).skip(1) {
+            self.faces.push(Face { vertices: [vertices[0], pair[0], pair[1]], material });
+        }
+    }
+
+    /// Index of the material with `name`, adding a blank one if it isn't known yet
+    pub fn material_named(&mut self, name: &str) -> usize {
+        match self.materials.iter().position(|m| m.name == name) {
+            Some(i) => i,
+            None => {
+                self.materials.push(Material { name: name.to_string(), ..Material::default() });
+                self.materials.len() - 1
+            },
+        }
+    }
+
+    /// Generate a dynlist that builds this mesh, using integer ids starting at `base_id`
+    pub fn to_items(&self, base_id: u32) -> Vec<DynListItem> {
+        let ids = MeshIds {
+            mtls: DynId(base_id),
+            vtx: DynId(base_id + 1),
+            faces: DynId(base_id + 2),
+            shape: DynId(base_id + 3),
+        };
+        let mut cmds = vec![Start, UseIntId(true)];
+
+        if !self.materials.is_empty() {
+            cmds.push(StartGroup(ids.mtls));
+            for (i, mtl) in self.materials.iter().enumerate() {
+                cmds.push(MakeObj(DObjType::D_MATERIAL, DynId::NULL));
+                cmds.push(SetId(i as u32));
+                cmds.extend(mtl.ambient.map(SetAmbient));
+                cmds.extend(mtl.diffuse.map(SetDiffuse));
+            }
+            cmds.push(EndGroup(ids.mtls));
+        }
+
+        cmds.push(StartGroup(ids.vtx));
+        for vtx in &self.vertices {
+            cmds.push(MakeVtx(vtx.pos));
+            cmds.extend(vtx.normal.map(SetNormal));
+            cmds.extend(vtx.st.map(|(s, t)| SetTxST(s, t)));
+        }
+        cmds.push(EndGroup(ids.vtx));
+
+        cmds.push(StartGroup(ids.faces));
+        for face in &self.faces {
+            cmds.push(MakeObj(DObjType::D_FACE, DynId::NULL));
+            cmds.extend(face.material.map(|m| SetMtl(Ptr::NULL, m as u32)));
+            for &v in &face.vertices {
+                cmds.push(ParamPtr(PtrParam::PARAM_OBJ_VTX, Ptr(v)));
+            }
+        }
+        cmds.push(EndGroup(ids.faces));
+        cmds.push(UseObj(ids.faces));
+        if !self.materials.is_empty() {
+            cmds.push(MapMtls(ids.mtls));
+        }
+        cmds.push(MapVtx(ids.vtx));
+
+        cmds.push(MakeObj(DObjType::D_SHAPE, ids.shape));
+        cmds.push(SetNodeGroup(ids.vtx));
+        cmds.push(SetPlaneGroup(ids.faces));
+        if !self.materials.is_empty() {
+            cmds.push(SetMtlGroup(ids.mtls));
+        }
+        cmds.push(Stop);
+
+        cmds.into_iter().map(DynListItem::from_cmd).collect()
+    }
+}
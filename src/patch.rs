@@ -0,0 +1,252 @@
+//! IPS and BPS patches between an original and a modified ROM of the same size
+
+use dynlist::{DynListIter, Limits, CMD_SIZE};
+use error::Error;
+use rom;
+use std::fmt;
+use std::io::{self, Cursor, Write};
+use std::str::FromStr;
+
+/// Patch file formats
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Format {
+    Ips,
+    Bps,
+}
+
+impl Format {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Format::Ips => "ips",
+            Format::Bps => "bps",
+        }
+    }
+}
+
+impl FromStr for Format {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "ips" => Ok(Format::Ips),
+            "bps" => Ok(Format::Bps),
+            _ => Err(format!("unknown patch format \"{}\" (expected ips or bps)", s)),
+        }
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.extension())
+    }
+}
+
+/// Largest offset an IPS record can start at
+const IPS_MAX_OFFSET: usize = 0xFF_FFFF;
+const IPS_MAX_RECORD: usize = 0xFFFF;
+/// A record at this offset would be read as the end of the patch
+const IPS_EOF: usize = 0x45_4F46;
+
+/// A copy of a ROM with one of its lists replaced
+#[derive(Debug, Clone)]
+pub struct Replaced {
+    pub rom: Vec<u8>,
+    /// Offset that the new list was written at
+    pub at: u64,
+    /// Size in bytes of the original list
+    pub slot: u64,
+    /// `false` if the ROM isn't big-endian, so its checksums weren't updated
+    pub crc_updated: bool,
+}
+
+/// Copy `original` with the list at `offset` replaced by the bytes of `list`, and update the
+/// checksums. The new list has to fit in the original's commands, unless it's written at `relocate`.
+pub fn replace_list(original: &[u8], offset: u64, list: &[u8], relocate: Option<u64>, limits: Limits) -> Result<Replaced, Error> {
+    let slot = DynListIter::from_reader(Cursor::new(original), offset)?
+        .limits(limits)
+        .collect::<Result<Vec<_>, _>>()?
+        .len() as u64 * CMD_SIZE;
+    let at = match relocate {
+        Some(at) => at,
+        None if list.len() as u64 > slot => return Err(Error::Rom {
+            msg: format!("the new list is {:#x} bytes, but the original at {:#X} is only {:#x}, so it has to be relocated",
+                list.len(), offset, slot),
+        }),
+        None => offset,
+    };
+    let mut patched = original.to_vec();
+    rom::write_list(&mut patched, at as usize, list)?;
+    let crc_updated = rom::update_crc(&mut patched);
+    Ok(Replaced { rom: patched, at, slot, crc_updated })
+}
+
+/// Write a patch in `format` that turns `source` into `target`
+pub fn write<W: Write>(format: Format, w: W, source: &[u8], target: &[u8]) -> io::Result<()> {
+    match format {
+        Format::Ips => ips(w, source, target),
+        Format::Bps => bps(w, source, target),
+    }
+}
+
+/// The ranges of bytes that differ between two buffers of the same size
+fn changed_runs(source: &[u8], target: &[u8]) -> Vec<(usize, usize)> {
+    let mut runs = Vec::new();
+    let mut i = 0;
+    while i < target.len() {
+        if source.get(i) == Some(&target[i]) {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        while i < target.len() && source.get(i) != Some(&target[i]) { i += 1; }
+        runs.push((start, i));
+    }
+    runs
+}
+
+/// Write an IPS patch. Fails if a change is past the 16 MiB that IPS can address.
+pub fn ips<W: Write>(mut w: W, source: &[u8], target: &[u8]) -> io::Result<()> {
+    if target.len() != source.len() {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "IPS patches can't change the size of a ROM"));
+    }
+    w.write_all(b"PATCH")?;
+    for (start, end) in changed_runs(source, target) {
+        let mut start = start;
+        while start < end {
+            // back up a byte, so that the record's offset isn't "EOF"
+            if start == IPS_EOF { start -= 1; }
+            let len = (end - start).min(IPS_MAX_RECORD);
+            if start > IPS_MAX_OFFSET {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                    format!("change at {:#X} is past the end of what an IPS patch can reach", start)));
+            }
+            w.write_all(&[(start >> 16) as u8, (start >> 8) as u8, start as u8])?;
+            w.write_all(&[(len >> 8) as u8, len as u8])?;
+            w.write_all(&target[start..start + len])?;
+            start += len;
+        }
+    }
+    w.write_all(b"EOF")?;
+    Ok(())
+}
+
+const BPS_SOURCE_READ: u64 = 0;
+const BPS_TARGET_READ: u64 = 1;
+
+/// Write a BPS patch, which copies unchanged bytes from the source and stores the rest
+pub fn bps<W: Write>(mut w: W, source: &[u8], target: &[u8]) -> io::Result<()> {
+    let mut patch = b"BPS1".to_vec();
+    bps_number(&mut patch, source.len() as u64);
+    bps_number(&mut patch, target.len() as u64);
+    bps_number(&mut patch, 0);  // no metadata
+
+    let mut pos = 0;
+    for (start, end) in changed_runs(source, target) {
+        if start > pos {
+            bps_number(&mut patch, ((start - pos - 1) as u64) << 2 | BPS_SOURCE_READ);
+        }
+        bps_number(&mut patch, ((end - start - 1) as u64) << 2 | BPS_TARGET_READ);
+        patch.extend_from_slice(&target[start..end]);
+        pos = end;
+    }
+    if target.len() > pos {
+        bps_number(&mut patch, ((target.len() - pos - 1) as u64) << 2 | BPS_SOURCE_READ);
+    }
+
+    patch.extend_from_slice(&crc32(source).to_le_bytes());
+    patch.extend_from_slice(&crc32(target).to_le_bytes());
+    let patch_crc = crc32(&patch);
+    patch.extend_from_slice(&patch_crc.to_le_bytes());
+    w.write_all(&patch)
+}
+
+/// BPS's variable length number encoding
+fn bps_number(out: &mut Vec<u8>, mut n: u64) {
+    loop {
+        let x = (n & 0x7F) as u8;
+        n >>= 7;
+        if n == 0 {
+            out.push(0x80 | x);
+            break;
+        }
+        out.push(x);
+        n -= 1;
+    }
+}
+
+/// The CRC-32 used by zip and BPS
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edited(len: usize, edits: &[(usize, u8)]) -> (Vec<u8>, Vec<u8>) {
+        let source = vec![0; len];
+        let mut target = source.clone();
+        for &(at, b) in edits { target[at] = b; }
+        (source, target)
+    }
+
+    #[test]
+    fn ips_records() {
+        let (source, target) = edited(16, &[(2, 0xAA), (3, 0xBB), (10, 0xCC)]);
+        let mut out = Vec::new();
+        ips(&mut out, &source, &target).unwrap();
+        let mut expected = b"PATCH".to_vec();
+        expected.extend_from_slice(&[0, 0, 2, 0, 2, 0xAA, 0xBB]);
+        expected.extend_from_slice(&[0, 0, 10, 0, 1, 0xCC]);
+        expected.extend_from_slice(b"EOF");
+        assert_eq!(out, expected);
+    }
+
+    #[test]
+    fn ips_backs_up_from_eof_offset() {
+        let (source, target) = edited(IPS_EOF + 4, &[(IPS_EOF, 0x01)]);
+        let mut out = Vec::new();
+        ips(&mut out, &source, &target).unwrap();
+        // the record starts a byte early, and rewrites the unchanged byte there
+        assert_eq!(out, b"PATCH\x45\x4F\x45\x00\x02\x00\x01EOF");
+    }
+
+    #[test]
+    fn crc32_check_value() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn bps_actions_and_crcs() {
+        let (source, target) = edited(8, &[(3, 0xFF)]);
+        let mut out = Vec::new();
+        bps(&mut out, &source, &target).unwrap();
+        let mut expected = b"BPS1".to_vec();
+        // sizes, no metadata, then read 3 from the source, 1 from the patch, and 4 from the source
+        expected.extend_from_slice(&[0x88, 0x88, 0x80, 0x88, 0x81, 0xFF, 0x8C]);
+        expected.extend_from_slice(&crc32(&source).to_le_bytes());
+        expected.extend_from_slice(&crc32(&target).to_le_bytes());
+        let patch_crc = crc32(&expected);
+        expected.extend_from_slice(&patch_crc.to_le_bytes());
+        assert_eq!(out, expected);
+    }
+
+    #[test]
+    fn bps_numbers() {
+        let encode = |n| {
+            let mut out = Vec::new();
+            bps_number(&mut out, n);
+            out
+        };
+        assert_eq!(encode(0), [0x80]);
+        assert_eq!(encode(127), [0xFF]);
+        assert_eq!(encode(128), [0x00, 0x80]);
+        assert_eq!(encode(300), [0x2C, 0x81]);
+    }
+}
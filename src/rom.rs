@@ -0,0 +1,204 @@
+//! Helpers for big-endian (.z64) N64 ROMs
+
+use byteorder::{ByteOrder, BE};
+use dynlist::{DynCmd, Ptr};
+use error::Error;
+
+/// First word of a big-endian ROM header
+pub const Z64_MAGIC: u32 = 0x8037_1240;
+/// Offset of the two header checksum words
+pub const CRC_OFFSET: usize = 0x10;
+/// The checksum covers the 1 MiB after the boot code
+const CRC_START: usize = 0x1000;
+const CRC_END: usize = CRC_START + 0x10_0000;
+const CIC_6102_SEED: u32 = 0xF8CA_4DDC;
+
+/// Is `rom` a big-endian ROM that's large enough to have its checksum updated
+pub fn is_z64(rom: &[u8]) -> bool {
+    rom.len() >= CRC_END && BE::read_u32(rom) == Z64_MAGIC
+}
+
+/// The header checksums that a CIC-6102 boot chip expects for `rom`
+pub fn crc_6102(rom: &[u8]) -> (u32, u32) {
+    let (mut t1, mut t2, mut t3, mut t4, mut t5, mut t6) =
+        (CIC_6102_SEED, CIC_6102_SEED, CIC_6102_SEED, CIC_6102_SEED, CIC_6102_SEED, CIC_6102_SEED);
+
+    for word in rom[CRC_START..CRC_END].chunks_exact(4) {
+        let d = BE::read_u32(word);
+        let (sum, carry) = t6.overflowing_add(d);
+        if carry { t4 = t4.wrapping_add(1); }
+        t6 = sum;
+        t3 ^= d;
+        let r = d.rotate_left(d & 0x1F);
+        t5 = t5.wrapping_add(r);
+        t2 ^= if t2 > d { r } else { t6 ^ d };
+        t1 = t1.wrapping_add(t5 ^ d);
+    }
+    (t6 ^ t4 ^ t3, t5 ^ t2 ^ t1)
+}
+
+/// Recalculate and write the header checksums. Returns `false` if `rom` isn't a big-endian ROM.
+pub fn update_crc(rom: &mut [u8]) -> bool {
+    if !is_z64(rom) { return false; }
+    let (crc1, crc2) = crc_6102(rom);
+    BE::write_u32(&mut rom[CRC_OFFSET..], crc1);
+    BE::write_u32(&mut rom[CRC_OFFSET + 4..], crc2);
+    true
+}
+
+/// Find the first `align`ed run of `len` bytes in `rom[start..end]` that are all 0x00 or all 0xFF
+pub fn find_free(rom: &[u8], start: usize, end: usize, len: usize, align: usize) -> Option<usize> {
+    let end = end.min(rom.len());
+    let mut at = start.next_multiple_of(align);
+    while at + len <= end {
+        let fill = rom[at];
+        if fill != 0x00 && fill != 0xFF {
+            at += align;
+            continue;
+        }
+        match rom[at..at + len].iter().position(|&b| b != fill) {
+            None => return Some(at),
+            // skip past the byte that broke the run
+            Some(bad) => at = (at + bad + 1).next_multiple_of(align),
+        }
+    }
+    None
+}
+
+/// Where to write a list in a ROM
+#[derive(Debug, Copy, Clone)]
+pub enum Place {
+    /// At this offset
+    At(usize),
+    /// At the first free run of bytes from `start`, up to `end` or the end of the ROM
+    Free { start: usize, end: Option<usize> },
+}
+
+/// A word in a ROM to change to the address of a list
+#[derive(Debug, Copy, Clone)]
+pub enum Pointer {
+    /// A pointer at this offset
+    Word(usize),
+    /// The list pointer of the `JumpToList` command at this offset
+    Jump(usize),
+}
+
+/// Where a list went in a ROM, and what was changed to point at it
+#[derive(Debug, Clone)]
+pub struct Injected {
+    /// Offset that the list was written at
+    pub at: usize,
+    /// Address of the list once the ROM is loaded
+    pub address: u32,
+    /// Offsets of the pointers that now hold `address`
+    pub pointers: Vec<usize>,
+    /// `false` if the ROM isn't big-endian, so its checksums weren't updated
+    pub crc_updated: bool,
+}
+
+/// Copy the bytes of a list into `rom` at `at`, if they fit
+pub fn write_list(rom: &mut [u8], at: usize, list: &[u8]) -> Result<(), Error> {
+    let end = at + list.len();
+    if end > rom.len() {
+        return Err(Error::Rom { msg: format!("the list would end at {:#X}, past the end of the {:#X} byte ROM", end, rom.len()) });
+    }
+    rom[at..end].copy_from_slice(list);
+    Ok(())
+}
+
+/// Write a list at `place`, change each of `pointers` to its address, and update the checksums.
+/// The address is worked out from `base_vram`, the address that `base_offset` is loaded at.
+pub fn inject(rom: &mut [u8], list: &[u8], place: Place, base_vram: u64, base_offset: u64, pointers: &[Pointer]) -> Result<Injected, Error> {
+    let bad = |msg: String| Error::Rom { msg };
+    let at = match place {
+        Place::At(at) => at,
+        Place::Free { start, end } => {
+            let end = end.unwrap_or(rom.len());
+            find_free(rom, start, end, list.len(), 8)
+                .ok_or_else(|| bad(format!("no run of {:#x} free bytes between {:#X} and {:#X}", list.len(), start, end)))?
+        },
+    };
+    write_list(rom, at, list)?;
+
+    if (at as u64) < base_offset {
+        return Err(bad(format!("the list at {:#X} is before the base offset {:#X}", at, base_offset)));
+    }
+    let address = (at as u64 - base_offset + base_vram) as u32;
+    let jump_id = DynCmd::Jump(Ptr(0)).info().id;
+    let mut locations = Vec::new();
+    for &pointer in pointers {
+        let loc = match pointer {
+            Pointer::Word(loc) => loc,
+            Pointer::Jump(loc) => {
+                let cmd = rom.get(loc..loc + 4).map(BE::read_u32);
+                if cmd != Some(jump_id) {
+                    return Err(bad(format!("there's no JumpToList command at {:#X}", loc)));
+                }
+                // the list pointer is the command's second word
+                loc + 4
+            },
+        };
+        if loc + 4 > rom.len() {
+            return Err(bad(format!("pointer at {:#X} is past the end of the ROM", loc)));
+        }
+        BE::write_u32(&mut rom[loc..], address);
+        locations.push(loc);
+    }
+    let crc_updated = update_crc(rom);
+    Ok(Injected { at, address, pointers: locations, crc_updated })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn blank_rom() -> Vec<u8> {
+        let mut rom = vec![0; CRC_END];
+        BE::write_u32(&mut rom, Z64_MAGIC);
+        rom
+    }
+
+    #[test]
+    fn crc_of_blank_image() {
+        // with every word zero, only t1 changes, by the seed each word
+        let rom = blank_rom();
+        assert_eq!(crc_6102(&rom), (CIC_6102_SEED, CIC_6102_SEED.wrapping_mul(0x4_0001)));
+    }
+
+    #[test]
+    fn crc_of_patterned_image() {
+        let mut rom: Vec<u8> = (0..CRC_END).map(|i| (i % 251) as u8).collect();
+        assert_eq!(crc_6102(&rom), (0xC91E_E9E4, 0xDDF5_6886));
+        // not a z64 until it has the magic word
+        assert!(!update_crc(&mut rom));
+        BE::write_u32(&mut rom, Z64_MAGIC);
+        assert!(update_crc(&mut rom));
+        assert_eq!(&rom[CRC_OFFSET..CRC_OFFSET + 8], &[0xC9, 0x1E, 0xE9, 0xE4, 0xDD, 0xF5, 0x68, 0x86]);
+    }
+
+    #[test]
+    fn free_runs_are_aligned_and_unbroken() {
+        let mut rom = vec![0xFF; 0x40];
+        rom[0x0C] = 0x12;
+        assert_eq!(find_free(&rom, 0, 0x40, 0x10, 8), Some(0x10));
+        assert_eq!(find_free(&rom, 0, 0x18, 0x10, 8), None);
+    }
+
+    #[test]
+    fn inject_repoints() {
+        let mut rom = blank_rom();
+        let jump = DynCmd::Jump(Ptr(0)).info().id;
+        BE::write_u32(&mut rom[0x2000..], jump);
+        let pointers = [Pointer::Word(0x3000), Pointer::Jump(0x2000)];
+        let injected = inject(&mut rom, &[1, 2, 3, 4], Place::At(0x4000), 0x8000_0000, 0x1000, &pointers).unwrap();
+        assert_eq!((injected.at, injected.address), (0x4000, 0x8000_3000));
+        assert_eq!(injected.pointers, [0x3000, 0x2004]);
+        assert_eq!(BE::read_u32(&rom[0x3000..]), 0x8000_3000);
+        assert_eq!(BE::read_u32(&rom[0x2004..]), 0x8000_3000);
+        assert_eq!(&rom[0x4000..0x4004], &[1, 2, 3, 4]);
+        assert_eq!(crc_6102(&rom), (BE::read_u32(&rom[CRC_OFFSET..]), BE::read_u32(&rom[CRC_OFFSET + 4..])));
+
+        let err = inject(&mut rom, &[1], Place::At(0x4000), 0, 0, &[Pointer::Jump(0x3000)]);
+        assert!(err.is_err());
+    }
+}
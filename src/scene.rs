@@ -0,0 +1,338 @@
+use dynlist::{DynCmd, DynListItem, DynId, DObjType, PtrParam, Vector, CMD_SIZE};
+use error::Error;
+use std::collections::HashMap;
+
+/// Everything a dynlist has set on a single object
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Obj {
+    pub kind: Option<DObjType>,
+    /// The dynobj id, or `None` for anonymous objects like vertices
+    pub id: Option<DynId>,
+    pub init_pos: Option<Vector>,
+    pub rel_pos: Option<Vector>,
+    pub world_pos: Option<Vector>,
+    pub normal: Option<Vector>,
+    pub scale: Option<Vector>,
+    pub rotation: Option<Vector>,
+    pub friction: Option<Vector>,
+    pub shape_offset: Option<Vector>,
+    pub att_offset: Option<Vector>,
+    pub cog: Option<Vector>,
+    pub ambient: Option<Vector>,
+    pub diffuse: Option<Vector>,
+    pub spring: Option<f32>,
+    pub st: Option<(f32, f32)>,
+    pub header_flag: Option<u32>,
+    pub flags: u32,
+    pub colour: Option<u32>,
+    pub obj_type: Option<u32>,
+    pub ctrl_type: Option<u32>,
+    /// Numeric id set by `SetId`, not the dynobj id
+    pub num_id: Option<u32>,
+    /// Material id of a face
+    pub material: Option<u32>,
+    /// Vertex indices of a face
+    pub vertices: Vec<u32>,
+    pub shape: Option<DynId>,
+    pub skin_shape: Option<DynId>,
+    pub node_group: Option<DynId>,
+    pub mtl_group: Option<DynId>,
+    pub plane_group: Option<DynId>,
+    /// (vertex index, weight) pairs of a joint
+    pub weights: Vec<(u32, f32)>,
+    /// Object index and attach flags
+    pub attached_to: Option<(usize, u32)>,
+    pub links: Vec<usize>,
+    /// Object indices of the members of a group
+    pub members: Vec<usize>,
+    /// Group id whose vertices are used by the faces in this group
+    pub map_vtx: Option<DynId>,
+    /// Group id whose materials are used by the faces in this group
+    pub map_mtl: Option<DynId>,
+    pub float_params: Vec<(u32, f32)>,
+}
+
+impl Obj {
+    fn new(kind: DObjType, id: Option<DynId>) -> Self {
+        Obj { kind: Some(kind), id, ..Default::default() }
+    }
+    pub fn is(&self, kind: DObjType) -> bool {
+        self.kind == Some(kind)
+    }
+    /// The best known position of the object
+    pub fn position(&self) -> Option<Vector> {
+        self.world_pos.or(self.init_pos).or(self.rel_pos)
+    }
+    /// Name for the object in exported files
+    pub fn name(&self, index: usize) -> String {
+        match (self.kind, self.id) {
+            (Some(k), Some(id)) if !id.is_null() => format!("{}_{:x}", kind_prefix(k), id.0),
+            (Some(k), _) => format!("{}_{}", kind_prefix(k), index),
+            _ => format!("obj_{}", index),
+        }
+    }
+}
+
+fn kind_prefix(kind: DObjType) -> String {
+    kind.to_string().trim_start_matches("D_").to_ascii_lowercase()
+}
+
+/// All of the objects created by interpreting a dynlist
+#[derive(Debug, Default)]
+pub struct Scene {
+    pub objs: Vec<Obj>,
+    names: HashMap<DynId, usize>,
+    /// Problems found while interpreting, like using an id that was never made
+    pub warnings: Vec<String>,
+}
+
+impl Scene {
+    /// Interpret a whole list that starts at `address`
+    pub fn from_items<I>(items: I, address: u64) -> Result<Self, Error>
+        where I: IntoIterator<Item = Result<DynListItem, Error>>
+    {
+        let mut interp = Interp::default();
+        for (index, item) in items.into_iter().enumerate() {
+            let item = item?;
+            if item.is_unk() {
+                let offset = address + index as u64 * CMD_SIZE;
+                return Err(Error::UnknownCmd { offset, index, raw: *item.raw() });
+            }
+            interp.step(item.cmd());
+        }
+        Ok(interp.finish())
+    }
+    pub fn get(&self, id: DynId) -> Option<usize> {
+        self.names.get(&id).cloned()
+    }
+    /// All objects of a type, with their indices
+    pub fn of_kind(&self, kind: DObjType) -> impl Iterator<Item = (usize, &Obj)> {
+        self.objs.iter().enumerate().filter(move |(_, o)| o.is(kind))
+    }
+    /// Members of the group named `id` that are of type `kind`
+    pub fn group_members(&self, id: DynId, kind: DObjType) -> Vec<usize> {
+        self.get(id)
+            .map(|g| self.objs[g].members.iter().cloned().filter(|&m| self.objs[m].is(kind)).collect())
+            .unwrap_or_default()
+    }
+    /// The innermost group that contains object `idx`
+    pub fn parent_group(&self, idx: usize) -> Option<usize> {
+        self.groups_containing(idx).next()
+    }
+    /// All groups that contain object `idx`, from innermost to outermost
+    pub fn groups_containing(&self, idx: usize) -> impl Iterator<Item = usize> {
+        let mut groups: Vec<usize> = self.of_kind(DObjType::D_GROUP)
+            .filter(|(_, g)| g.members.contains(&idx))
+            .map(|(i, _)| i)
+            .collect();
+        groups.sort_by_key(|&g| self.objs[g].members.len());
+        groups.into_iter()
+    }
+    /// The shape that uses the face group containing `idx`
+    pub fn shape_of(&self, idx: usize) -> Option<usize> {
+        self.groups_containing(idx)
+            .filter_map(|g| self.objs[g].id)
+            .filter_map(|id| self.of_kind(DObjType::D_SHAPE).find(|(_, s)| s.plane_group == Some(id)))
+            .map(|(i, _)| i)
+            .next()
+    }
+    /// The vertices that the indices of a face refer to. These come from the group
+    /// mapped with `MapVertices`, the node group of the face's shape, or every vertex in the list.
+    /// Returns the first out of range index as an error.
+    pub fn face_vertices(&self, idx: usize) -> Result<Vec<usize>, u32> {
+        let group = self.groups_containing(idx)
+            .filter_map(|g| self.objs[g].map_vtx)
+            .next()
+            .or_else(|| self.shape_of(idx).and_then(|s| self.objs[s].node_group));
+        let vertices = match group {
+            Some(g) => self.group_members(g, DObjType::D_VERTEX),
+            None => self.of_kind(DObjType::D_VERTEX).map(|(i, _)| i).collect(),
+        };
+        self.objs[idx].vertices.iter()
+            .map(|&v| vertices.get(v as usize).cloned().ok_or(v))
+            .collect()
+    }
+    /// The material that a face uses, matched on the number set with `SetId`. Materials from
+    /// the group mapped with `MapMaterials` or the face's shape are checked first.
+    pub fn face_material(&self, idx: usize) -> Option<usize> {
+        let id = self.objs[idx].material?;
+        let group = self.groups_containing(idx)
+            .filter_map(|g| self.objs[g].map_mtl)
+            .next()
+            .or_else(|| self.shape_of(idx).and_then(|s| self.objs[s].mtl_group));
+        group.map(|g| self.group_members(g, DObjType::D_MATERIAL))
+            .unwrap_or_default()
+            .into_iter()
+            .find(|&m| self.objs[m].num_id == Some(id))
+            .or_else(|| self.of_kind(DObjType::D_MATERIAL)
+                .find(|(_, m)| m.num_id == Some(id))
+                .map(|(i, _)| i))
+    }
+}
+
+/// Step by step interpreter state for a dynlist
+#[derive(Debug, Default)]
+pub(crate) struct Interp {
+    scene: Scene,
+    cur: Option<usize>,
+    /// Open groups, and their index in the object list
+    groups: Vec<(DynId, usize)>,
+}
+
+impl Interp {
+    pub fn finish(mut self) -> Scene {
+        for (id, _) in self.groups.drain(..) {
+            self.scene.warnings.push(format!("group {:#x} was never ended", id.0));
+        }
+        self.scene
+    }
+    /// The innermost group that is still being built
+    pub fn open_group(&self) -> Option<DynId> {
+        self.groups.last().map(|&(id, _)| id)
+    }
+    /// The object that commands are currently acting on, and its index
+    pub fn current(&self) -> Option<(usize, &Obj)> {
+        self.cur.map(|idx| (idx, &self.scene.objs[idx]))
+    }
+
+    fn make(&mut self, kind: DObjType, id: Option<DynId>) -> usize {
+        let idx = self.scene.objs.len();
+        self.scene.objs.push(Obj::new(kind, id));
+        if let Some(id) = id.filter(|id| !id.is_null()) {
+            if self.scene.names.insert(id, idx).is_some() {
+                self.warn(format!("id {:#x} was used to make more than one object", id.0));
+            }
+        }
+        self.cur = Some(idx);
+        idx
+    }
+    fn lookup(&mut self, id: DynId) -> Option<usize> {
+        let found = self.scene.get(id);
+        if found.is_none() { self.warn(format!("no object with id {:#x}", id.0)); }
+        found
+    }
+    fn warn(&mut self, msg: String) {
+        self.scene.warnings.push(msg);
+    }
+    /// Start collecting objects into a group. A net's subgroup shares the net's id,
+    /// so it isn't registered under that name.
+    fn start_group(&mut self, id: DynId, named: bool) {
+        let idx = self.make(DObjType::D_GROUP, if named { Some(id) } else { None });
+        self.groups.push((id, idx));
+    }
+    fn end_group(&mut self, id: DynId) {
+        match self.groups.iter().rposition(|&(g, _)| g == id) {
+            Some(pos) => {
+                let (_, g) = self.groups.remove(pos);
+                let end = self.scene.objs.len();
+                // keep anything added with AddToGroup while the group was open
+                let added = std::mem::take(&mut self.scene.objs[g].members);
+                let mut members: Vec<usize> = (g + 1..end).collect();
+                for idx in added {
+                    if !members.contains(&idx) { members.push(idx); }
+                }
+                self.scene.objs[g].members = members;
+            },
+            None => self.warn(format!("ending group {:#x}, which was never started", id.0)),
+        }
+    }
+
+    /// Process a single command
+    pub fn step(&mut self, cmd: &DynCmd) {
+        use dynlist::DynCmd::*;
+        match *cmd {
+            MakeObj(kind, id)   => { self.make(kind, Some(id)); },
+            MakeVtx(v)          => {
+                let idx = self.make(DObjType::D_VERTEX, None);
+                self.scene.objs[idx].init_pos = Some(v);
+            },
+            NetFromId(id)       => {
+                let idx = self.make(DObjType::D_NET, None);
+                self.scene.objs[idx].shape = Some(id);
+            },
+            NetFromPtr(..)      => { self.make(DObjType::D_NET, None); },
+            StartGroup(id)      => self.start_group(id, true),
+            EndGroup(id) | EndNetGrp(id) => self.end_group(id),
+            NetSubGrp(_, id)    => {
+                self.make(DObjType::D_NET, Some(id));
+                self.start_group(id, false);
+            },
+            AttNetJoint(_, id)  => {
+                let net = self.open_group().and_then(|g| self.scene.get(g));
+                let joint = self.make(DObjType::D_JOINT, Some(id));
+                if let Some(net) = net.filter(|&n| n != joint) {
+                    self.scene.objs[joint].attached_to = Some((net, 0));
+                }
+            },
+            UseObj(id)          => self.cur = self.lookup(id),
+            AddToGroup(id)      => {
+                if let (Some(g), Some(cur)) = (self.lookup(id), self.cur) {
+                    if !self.scene.objs[g].members.contains(&cur) {
+                        self.scene.objs[g].members.push(cur);
+                    }
+                }
+            },
+            Attach(id)          => self.attach(id, 0),
+            AttachTo(flag, id)  => self.attach(id, flag),
+            LinkWith(id)        => {
+                if let Some(target) = self.lookup(id) {
+                    self.with_cur(|o| o.links.push(target));
+                }
+            },
+            ParamPtr(PtrParam::PARAM_OBJ_VTX, p) => self.with_cur(|o| o.vertices.push(p.0)),
+            _ => self.set_property(cmd),
+        }
+    }
+
+    fn attach(&mut self, id: DynId, flag: u32) {
+        if let Some(target) = self.lookup(id) {
+            self.with_cur(|o| o.attached_to = Some((target, flag)));
+        }
+    }
+
+    fn with_cur<F: FnOnce(&mut Obj)>(&mut self, f: F) {
+        match self.cur {
+            Some(cur) => f(&mut self.scene.objs[cur]),
+            None => self.warn("command used without a current object".to_string()),
+        }
+    }
+
+    /// Commands that only modify the current object
+    fn set_property(&mut self, cmd: &DynCmd) {
+        use dynlist::DynCmd::*;
+        match *cmd {
+            SetInitPos(v)       => self.with_cur(|o| o.init_pos = Some(v)),
+            SetRelPos(v)        => self.with_cur(|o| o.rel_pos = Some(v)),
+            SetWorldPos(v)      => self.with_cur(|o| o.world_pos = Some(v)),
+            SetNormal(v)        => self.with_cur(|o| o.normal = Some(v)),
+            SetScale(v)         => self.with_cur(|o| o.scale = Some(v)),
+            SetRotation(v)      => self.with_cur(|o| o.rotation = Some(v)),
+            SetFriction(v)      => self.with_cur(|o| o.friction = Some(v)),
+            SetShpOff(v)        => self.with_cur(|o| o.shape_offset = Some(v)),
+            SetAttOff(v)        => self.with_cur(|o| o.att_offset = Some(v)),
+            SetCoG(v)           => self.with_cur(|o| o.cog = Some(v)),
+            SetAmbient(v)       => self.with_cur(|o| o.ambient = Some(v)),
+            SetDiffuse(v)       => self.with_cur(|o| o.diffuse = Some(v)),
+            SetSpring(f)        => self.with_cur(|o| o.spring = Some(f)),
+            SetTxST(s, t)       => self.with_cur(|o| o.st = Some((s, t))),
+            SetHeaderFlag(f)    => self.with_cur(|o| o.header_flag = Some(f)),
+            SetFlag(f)          => self.with_cur(|o| o.flags |= f),
+            ClearFlag(f)        => self.with_cur(|o| o.flags &= !f),
+            SetColourNum(n)     => self.with_cur(|o| o.colour = Some(n)),
+            SetType(n)          => self.with_cur(|o| o.obj_type = Some(n)),
+            SetCtrlType(n)      => self.with_cur(|o| o.ctrl_type = Some(n)),
+            SetId(n)            => self.with_cur(|o| o.num_id = Some(n)),
+            SetMtl(_, n)        => self.with_cur(|o| o.material = Some(n)),
+            SetShpPtr(id)       => self.with_cur(|o| o.shape = Some(id)),
+            SetSkinShape(id)    => self.with_cur(|o| o.skin_shape = Some(id)),
+            SetNodeGroup(id)    => self.with_cur(|o| o.node_group = Some(id)),
+            SetMtlGroup(id)     => self.with_cur(|o| o.mtl_group = Some(id)),
+            SetPlaneGroup(id)   => self.with_cur(|o| o.plane_group = Some(id)),
+            SetSkinWgt(v, w)    => self.with_cur(|o| o.weights.push((v, w))),
+            MapVtx(id)          => self.with_cur(|o| o.map_vtx = Some(id)),
+            MapMtls(id)         => self.with_cur(|o| o.map_mtl = Some(id)),
+            ParamF(p, f)        => self.with_cur(|o| o.float_params.push((p, f))),
+            _ => (),
+        }
+    }
+}
@@ -0,0 +1,594 @@
+//! Dynlists read from source text (C or gas), before any strings or symbols have addresses.
+//!
+//! Each command keeps its raw words, with zero in any word that holds the address of a string
+//! or symbol, and a `Reloc` saying what belongs there. Linking fills those words in; until then,
+//! `SourceList::items` gives every string and symbol a placeholder so the list can still be
+//! checked, diffed, and interpreted.
+
+use asm;
+use c89;
+use dynlist::{self, DynListItem};
+use error::Error;
+use std::collections::HashMap;
+use std::error;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+/// First placeholder value given to a string or symbol
+pub const PLACEHOLDER_BASE: u32 = 0xFF00_0000;
+
+/// What a word of a command should hold the address of
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum Target {
+    /// A string literal, like a `char *` object id
+    Str(String),
+    /// A symbol defined somewhere else, like a list for `JumpToList`
+    Sym(String),
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Target::Str(s) => write!(f, "{:?}", s),
+            Target::Sym(s) => write!(f, "{}", s),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reloc {
+    /// Index of the command word
+    pub word: usize,
+    pub target: Target,
+}
+
+/// A command from a source file
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourceCmd {
+    pub raw: [u32; 6],
+    pub relocs: Vec<Reloc>,
+    /// Where the command is in the source, starting from 1
+    pub line: usize,
+    pub col: usize,
+}
+
+/// A named list of commands from a source file
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourceList {
+    pub name: String,
+    pub cmds: Vec<SourceCmd>,
+}
+
+impl SourceList {
+    /// Do any commands refer to a string or symbol
+    pub fn has_relocs(&self) -> bool {
+        self.cmds.iter().any(|c| !c.relocs.is_empty())
+    }
+    /// Every string and symbol the list refers to, in order of first use
+    pub fn targets(&self) -> Vec<&Target> {
+        let mut targets = Vec::new();
+        for reloc in self.cmds.iter().flat_map(|c| &c.relocs) {
+            if !targets.contains(&&reloc.target) { targets.push(&reloc.target); }
+        }
+        targets
+    }
+    /// The decoded commands, with each string or symbol replaced by a placeholder
+    /// (`PLACEHOLDER_BASE` and up) that's the same wherever the same target is used
+    pub fn items(&self) -> Vec<DynListItem> {
+        let placeholders: HashMap<&Target, u32> = self.targets().into_iter()
+            .enumerate()
+            .map(|(i, t)| (t, PLACEHOLDER_BASE + i as u32))
+            .collect();
+        self.cmds.iter().map(|cmd| {
+            let mut raw = cmd.raw;
+            for reloc in &cmd.relocs {
+                raw[reloc.word] = placeholders[&reloc.target];
+            }
+            DynListItem::from_raw(raw)
+        }).collect()
+    }
+}
+
+/// Read every list in a gas (.s) source, or else a C source
+pub fn read(path: &Path) -> Result<Vec<SourceList>, Error> {
+    let text = fs::read_to_string(path).map_err(|err| Error::File { path: path.display().to_string(), err })?;
+    let (what, parsed) = match path.extension() {
+        Some(ext) if ext == "s" => ("gas source", asm::parse(&text)),
+        _ => ("C source", c89::parse(&text)),
+    };
+    parsed.map_err(|e| Error::Syntax { what, line: Some(e.line), msg: format!("column {}: {}", e.col, e.msg) })
+}
+
+/// Something wrong with a source file, and where it is
+#[derive(Debug, Clone)]
+pub struct ParseError {
+    pub line: usize,
+    pub col: usize,
+    pub msg: String,
+}
+
+impl ParseError {
+    pub fn new<S: Into<String>>(line: usize, col: usize, msg: S) -> Self {
+        ParseError { line, col, msg: msg.into() }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}, column {}: {}", self.line, self.col, self.msg)
+    }
+}
+
+impl error::Error for ParseError {}
+
+/// A parsed macro argument
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Value {
+    Int(i64),
+    Float(f64),
+    Addr(Target),
+}
+
+/// Place the arguments of a command macro into its words, like the generated macros do.
+/// Words 1 and 2 are integers (or addresses), and the vector words are floats.
+pub(crate) fn encode(name: &str, args: Vec<Value>, line: usize, col: usize) -> Result<SourceCmd, ParseError> {
+    let err = |msg: String| ParseError::new(line, col, msg);
+    let info = dynlist::DynCmd::lookup(name).ok_or_else(|| err(format!("unknown command `{}`", name)))?;
+    let slots = info.kind.slots();
+    if args.len() != slots.len() {
+        return Err(err(format!("{} takes {} arguments, but {} were given", name, slots.len(), args.len())));
+    }
+    let mut words = [Some(Value::Int(i64::from(info.id))), None, None, None, None, None];
+    for (&slot, arg) in slots.iter().zip(args) {
+        words[slot] = Some(arg);
+    }
+    encode_words(words, line, col)
+}
+
+/// Build a command from the value of each of its words, like the `DynListCmd` base macro.
+/// Missing words are zero.
+pub(crate) fn encode_words(words: [Option<Value>; 6], line: usize, col: usize) -> Result<SourceCmd, ParseError> {
+    let err = |msg: String| ParseError::new(line, col, msg);
+    let mut cmd = SourceCmd { raw: [0; 6], relocs: Vec::new(), line, col };
+    for (slot, word) in words.iter().enumerate() {
+        let arg = match word {
+            Some(arg) => arg.clone(),
+            None => continue,
+        };
+        cmd.raw[slot] = match arg {
+            // only the two pointer words can hold an address
+            Value::Addr(target) if slot == 1 || slot == 2 => {
+                cmd.relocs.push(Reloc { word: slot, target });
+                0
+            },
+            Value::Addr(target) => return Err(err(format!("expected a number, found {}", target))),
+            Value::Int(n) if slot >= 3 => (n as f32).to_bits(),
+            Value::Float(f) if slot >= 3 => (f as f32).to_bits(),
+            Value::Int(n) if n >= i64::from(i32::MIN) && n <= i64::from(u32::MAX) => n as u32,
+            Value::Int(n) => return Err(err(format!("{} is not a 32-bit integer", n))),
+            Value::Float(f) => return Err(err(format!("expected an integer, found {:?}", f))),
+        };
+    }
+    dynlist::check_raw(&cmd.raw).map_err(err)?;
+    Ok(cmd)
+}
+
+/// A piece of source text
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Tok {
+    Ident(String),
+    Num(String),
+    Str(String),
+    Punct(&'static str),
+    /// Only made for line based sources, like gas
+    Newline,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Token {
+    pub tok: Tok,
+    pub line: usize,
+    pub col: usize,
+}
+
+const PUNCTS: &[&str] = &["<<", ">>", "(", ")", "[", "]", "{", "}", ",", ";", ":", "=", "&", "|", "+", "-", "~", "*", "<", ">", "\\"];
+
+/// Split source text into tokens, skipping `//` and `/* */` comments and any line starting
+/// with `#` (C preprocessor lines, or gas comments). Newlines are only kept if `newlines` is set.
+pub(crate) fn tokenize(text: &str, newlines: bool) -> Result<Vec<Token>, ParseError> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut toks = Vec::new();
+    let (mut i, mut line, mut line_start) = (0, 1, 0);
+    let mut at_line_start = true;
+    while i < chars.len() {
+        let c = chars[i];
+        let col = i - line_start + 1;
+        let next = chars.get(i + 1).cloned();
+        if c == '\n' {
+            if newlines { toks.push(Token { tok: Tok::Newline, line, col }); }
+            i += 1;
+            line += 1;
+            line_start = i;
+            at_line_start = true;
+            continue;
+        }
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+        if c == '#' || (c == '/' && next == Some('/')) {
+            // to the end of the line, following any `\` continuations of a preprocessor line
+            while i < chars.len() && chars[i] != '\n' {
+                if c == '#' && at_line_start && chars[i] == '\\' && chars.get(i + 1) == Some(&'\n') {
+                    i += 1;
+                    line += 1;
+                    line_start = i + 1;
+                }
+                i += 1;
+            }
+            continue;
+        }
+        at_line_start = false;
+        if c == '/' && next == Some('*') {
+            let start = (line, col);
+            i += 2;
+            loop {
+                match chars.get(i) {
+                    None => return Err(ParseError::new(start.0, start.1, "comment is never closed")),
+                    Some('*') if chars.get(i + 1) == Some(&'/') => break,
+                    Some('\n') => {
+                        line += 1;
+                        line_start = i + 1;
+                    },
+                    _ => (),
+                }
+                i += 1;
+            }
+            i += 2;
+            continue;
+        }
+
+        let tok = if c.is_ascii_digit() || (c == '.' && next.is_some_and(|n| n.is_ascii_digit())) {
+            let start = i;
+            while i < chars.len() {
+                let d = chars[i];
+                let exponent = (d == '+' || d == '-')
+                    && matches!(chars[i - 1], 'e' | 'E')
+                    && !chars[start..i].iter().any(|&x| x == 'x' || x == 'X');
+                if d.is_ascii_alphanumeric() || d == '.' || d == '_' || exponent { i += 1; } else { break; }
+            }
+            Tok::Num(chars[start..i].iter().collect())
+        } else if c.is_ascii_alphabetic() || c == '_' || c == '.' || c == '$' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || matches!(chars[i], '_' | '.' | '$')) { i += 1; }
+            Tok::Ident(chars[start..i].iter().collect())
+        } else if c == '"' {
+            i += 1;
+            let mut s = String::new();
+            loop {
+                match chars.get(i) {
+                    None | Some('\n') => return Err(ParseError::new(line, col, "string is never closed")),
+                    Some('"') => break,
+                    Some('\\') => {
+                        i += 1;
+                        let esc = chars.get(i).cloned().unwrap_or('\\');
+                        s.push(match esc {
+                            'n' => '\n',
+                            't' => '\t',
+                            'r' => '\r',
+                            'x' => {
+                                let hex: String = chars[i + 1..].iter().take_while(|h| h.is_ascii_hexdigit()).take(2).collect();
+                                i += hex.len();
+                                u8::from_str_radix(&hex, 16).map(char::from)
+                                    .map_err(|_| ParseError::new(line, i - line_start, "bad \\x escape"))?
+                            },
+                            '0'..='7' => {
+                                let oct: String = chars[i..].iter().take_while(|o| o.is_digit(8)).take(3).collect();
+                                i += oct.len() - 1;
+                                char::from(u8::from_str_radix(&oct, 8).unwrap_or(0))
+                            },
+                            other => other,
+                        });
+                    },
+                    Some(&other) => s.push(other),
+                }
+                i += 1;
+            }
+            i += 1;
+            Tok::Str(s)
+        } else {
+            let rest: String = chars[i..chars.len().min(i + 2)].iter().collect();
+            match PUNCTS.iter().find(|p| rest.starts_with(*p)) {
+                Some(p) => {
+                    i += p.len();
+                    Tok::Punct(p)
+                },
+                None => return Err(ParseError::new(line, col, format!("unexpected character `{}`", c))),
+            }
+        };
+        toks.push(Token { tok, line, col });
+    }
+    Ok(toks)
+}
+
+/// Type names that can start a C cast
+const CAST_TYPES: &[&str] = &["void", "char", "int", "unsigned", "signed", "long", "short", "float",
+    "const", "struct", "u8", "s8", "u16", "s16", "u32", "s32", "uintptr_t", "intptr_t", "DynId"];
+
+/// Reads macro arguments out of a list of tokens
+pub(crate) struct Cursor<'a> {
+    toks: &'a [Token],
+    pub pos: usize,
+    /// Constants defined in the source, like gas `.set`s
+    pub consts: HashMap<String, Value>,
+}
+
+impl<'a> Cursor<'a> {
+    pub fn new(toks: &'a [Token]) -> Self {
+        Cursor { toks, pos: 0, consts: HashMap::new() }
+    }
+    pub fn peek(&self) -> Option<&'a Tok> {
+        self.toks.get(self.pos).map(|t| &t.tok)
+    }
+    pub fn peek_at(&self, n: usize) -> Option<&'a Tok> {
+        self.toks.get(self.pos + n).map(|t| &t.tok)
+    }
+    pub fn bump(&mut self) -> Option<&'a Token> {
+        let tok = self.toks.get(self.pos);
+        if tok.is_some() { self.pos += 1; }
+        tok
+    }
+    /// Where the next token is, or the end of the last one
+    pub fn here(&self) -> (usize, usize) {
+        self.toks.get(self.pos).or_else(|| self.toks.last())
+            .map(|t| (t.line, t.col))
+            .unwrap_or((1, 1))
+    }
+    pub fn error<S: Into<String>>(&self, msg: S) -> ParseError {
+        let (line, col) = self.here();
+        ParseError::new(line, col, msg)
+    }
+    /// Take the next token if it's the punctuation `p`
+    pub fn eat(&mut self, p: &str) -> bool {
+        match self.peek() {
+            Some(Tok::Punct(q)) if *q == p => {
+                self.pos += 1;
+                true
+            },
+            _ => false,
+        }
+    }
+    pub fn expect(&mut self, p: &str) -> Result<(), ParseError> {
+        if self.eat(p) { Ok(()) } else { Err(self.error(format!("expected `{}`, found {}", p, self.describe()))) }
+    }
+    pub fn ident(&mut self) -> Result<&'a str, ParseError> {
+        match self.peek() {
+            Some(Tok::Ident(name)) => {
+                self.pos += 1;
+                Ok(name)
+            },
+            _ => Err(self.error(format!("expected a name, found {}", self.describe()))),
+        }
+    }
+    /// The next token, for error messages
+    pub fn describe(&self) -> String {
+        match self.peek() {
+            None => "the end of the file".to_string(),
+            Some(Tok::Ident(s)) => format!("`{}`", s),
+            Some(Tok::Num(s)) => format!("`{}`", s),
+            Some(Tok::Str(s)) => format!("{:?}", s),
+            Some(Tok::Punct(p)) => format!("`{}`", p),
+            Some(Tok::Newline) => "the end of the line".to_string(),
+        }
+    }
+
+    /// Parse an argument expression: numbers, named constants, strings, symbols (`sym` or `&sym`),
+    /// C casts, and `|`, `<<`, `>>`, `+`, `-`, `~` on integers
+    pub fn expr(&mut self) -> Result<Value, ParseError> {
+        let mut lhs = self.shift()?;
+        while self.eat("|") {
+            let rhs = self.shift()?;
+            lhs = self.int_op(lhs, rhs, "|", |a, b| a | b)?;
+        }
+        Ok(lhs)
+    }
+    fn shift(&mut self) -> Result<Value, ParseError> {
+        let mut lhs = self.additive()?;
+        loop {
+            if self.eat("<<") {
+                let rhs = self.additive()?;
+                lhs = self.int_op(lhs, rhs, "<<", |a, b| a << (b & 63))?;
+            } else if self.eat(">>") {
+                let rhs = self.additive()?;
+                lhs = self.int_op(lhs, rhs, ">>", |a, b| a >> (b & 63))?;
+            } else {
+                return Ok(lhs);
+            }
+        }
+    }
+    fn additive(&mut self) -> Result<Value, ParseError> {
+        let mut lhs = self.unary()?;
+        loop {
+            let sign = if self.eat("+") { 1.0 } else if self.eat("-") { -1.0 } else { return Ok(lhs) };
+            let rhs = self.unary()?;
+            lhs = match (lhs, rhs) {
+                (Value::Int(a), Value::Int(b)) => Value::Int(a + sign as i64 * b),
+                (Value::Int(a), Value::Float(b)) => Value::Float(a as f64 + sign * b),
+                (Value::Float(a), Value::Int(b)) => Value::Float(a + sign * b as f64),
+                (Value::Float(a), Value::Float(b)) => Value::Float(a + sign * b),
+                _ => return Err(self.error("can't add to or subtract from an address")),
+            };
+        }
+    }
+    fn int_op<F: Fn(i64, i64) -> i64>(&self, a: Value, b: Value, op: &str, f: F) -> Result<Value, ParseError> {
+        match (a, b) {
+            (Value::Int(a), Value::Int(b)) => Ok(Value::Int(f(a, b))),
+            _ => Err(self.error(format!("`{}` only works on integers", op))),
+        }
+    }
+    fn unary(&mut self) -> Result<Value, ParseError> {
+        if self.eat("-") {
+            return match self.unary()? {
+                Value::Int(n) => Ok(Value::Int(-n)),
+                Value::Float(f) => Ok(Value::Float(-f)),
+                Value::Addr(..) => Err(self.error("can't negate an address")),
+            };
+        }
+        if self.eat("~") {
+            return match self.unary()? {
+                Value::Int(n) => Ok(Value::Int(i64::from(!(n as u32)))),
+                _ => Err(self.error("`~` only works on integers")),
+            };
+        }
+        if self.eat("&") {
+            let name = self.ident()?;
+            return Ok(Value::Addr(Target::Sym(name.to_string())));
+        }
+        if self.eat("(") {
+            if self.is_cast() {
+                while !self.eat(")") { self.bump(); }
+                return self.unary();
+            }
+            let val = self.expr()?;
+            self.expect(")")?;
+            return Ok(val);
+        }
+        self.primary()
+    }
+    /// Is the text after a `(` a type, like `void *)`
+    fn is_cast(&self) -> bool {
+        let mut n = 0;
+        let mut pointer = false;
+        let mut first = None;
+        loop {
+            match self.peek_at(n) {
+                Some(Tok::Ident(name)) => { first = first.or(Some(name.as_str())); },
+                Some(Tok::Punct("*")) => pointer = true,
+                Some(Tok::Punct(")")) => break,
+                _ => return false,
+            }
+            n += 1;
+        }
+        match first {
+            Some(ty) => pointer || CAST_TYPES.contains(&ty),
+            None => false,
+        }
+    }
+    fn primary(&mut self) -> Result<Value, ParseError> {
+        let (line, col) = self.here();
+        match self.bump().map(|t| &t.tok) {
+            Some(Tok::Num(text)) => parse_number(text).ok_or_else(|| ParseError::new(line, col, format!("bad number `{}`", text))),
+            Some(Tok::Str(s)) => {
+                // C joins string literals that are next to each other
+                let mut s = s.clone();
+                while let Some(Tok::Str(more)) = self.peek() {
+                    s.push_str(more);
+                    self.pos += 1;
+                }
+                Ok(Value::Addr(Target::Str(s)))
+            },
+            Some(Tok::Ident(name)) => {
+                // the `ID<...>` and `Ptr<...>` forms written by `dump`
+                if (name == "ID" || name == "Ptr") && self.eat("<") {
+                    let val = self.shift()?;
+                    self.expect(">")?;
+                    return Ok(val);
+                }
+                if let Some(val) = self.consts.get(name.as_str()) {
+                    return Ok(val.clone());
+                }
+                Ok(match dynlist::lookup_constant(name) {
+                    Some(n) => Value::Int(i64::from(n)),
+                    None => Value::Addr(Target::Sym(name.clone())),
+                })
+            },
+            _ => {
+                self.pos = self.pos.saturating_sub(1);
+                Err(ParseError::new(line, col, format!("expected a value, found {}", self.describe())))
+            },
+        }
+    }
+}
+
+/// Parse a C or gas number, ignoring C's `u`/`l`/`f` suffixes
+pub(crate) fn parse_number(text: &str) -> Option<Value> {
+    let lower = text.to_ascii_lowercase();
+    if let Some(hex) = lower.strip_prefix("0x") {
+        return i64::from_str_radix(hex.trim_end_matches(['u', 'l']), 16).ok().map(Value::Int);
+    }
+    if lower.contains('.') || lower.contains('e') || lower.ends_with('f') {
+        return lower.trim_end_matches('f').parse().ok().map(Value::Float);
+    }
+    let int = lower.trim_end_matches(['u', 'l']);
+    if int.len() > 1 && int.starts_with('0') {
+        i64::from_str_radix(&int[1..], 8).ok().map(Value::Int)
+    } else {
+        int.parse().ok().map(Value::Int)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn numbers() {
+        assert_eq!(parse_number("0x1Fu"), Some(Value::Int(0x1f)));
+        assert_eq!(parse_number("017"), Some(Value::Int(15)));
+        assert_eq!(parse_number("0"), Some(Value::Int(0)));
+        assert_eq!(parse_number("42l"), Some(Value::Int(42)));
+        assert_eq!(parse_number("0.5f"), Some(Value::Float(0.5)));
+        assert_eq!(parse_number("1e3"), Some(Value::Float(1000.0)));
+        assert_eq!(parse_number("0xg"), None);
+    }
+
+    #[test]
+    fn expressions() {
+        let value = |text: &str| {
+            let toks = tokenize(text, false).unwrap();
+            Cursor::new(&toks).expr()
+        };
+        assert_eq!(value("OBJ_INVISIBLE | 1 << 3").unwrap(), Value::Int(0x0a));
+        assert_eq!(value("~0").unwrap(), Value::Int(0xffff_ffff));
+        assert_eq!(value("(float) -1 + 0.5").unwrap(), Value::Float(-0.5));
+        assert_eq!(value("(void *) &sym").unwrap(), Value::Addr(Target::Sym("sym".to_string())));
+        assert!(value("\"a\" + 1").is_err());
+    }
+
+    #[test]
+    fn same_target_same_placeholder() {
+        let lists = c89::parse(r#"struct DynList l[] = {
+            MakeDynObj(D_JOINT, "jaw"),
+            UseObj("jaw"),
+            JumpToList(teeth),
+        };"#).unwrap();
+        let list = &lists[0];
+        assert!(list.has_relocs());
+        assert_eq!(list.targets(), [&Target::Str("jaw".to_string()), &Target::Sym("teeth".to_string())]);
+        let raw: Vec<u32> = list.items().iter().map(|i| i.raw()[1]).collect();
+        assert_eq!(raw, [PLACEHOLDER_BASE, PLACEHOLDER_BASE, PLACEHOLDER_BASE + 1]);
+    }
+
+    #[test]
+    fn read_picks_the_parser() {
+        let dir = env::temp_dir().join(format!("dynlist-source-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let (c, s) = (dir.join("list.c"), dir.join("list.s"));
+        fs::write(&c, "struct DynList l[] = { StartList(), StopList() };\n").unwrap();
+        fs::write(&s, "l:\n    StartList\n    StopList\n").unwrap();
+        let lists: Vec<_> = [&c, &s].iter().map(|p| read(p).unwrap()).collect();
+        assert_eq!(lists[0][0].cmds.len(), 2);
+        assert_eq!(lists[0][0].name, lists[1][0].name);
+        let raw = |list: &SourceList| list.cmds.iter().map(|c| c.raw).collect::<Vec<_>>();
+        assert_eq!(raw(&lists[0][0]), raw(&lists[1][0]));
+
+        fs::write(&s, "l:\n    StartList 1\n").unwrap();
+        match read(&s) {
+            Err(Error::Syntax { what, line, .. }) => assert_eq!((what, line), ("gas source", Some(2))),
+            other => panic!("expected a syntax error, got {:?}", other),
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
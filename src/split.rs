@@ -0,0 +1,141 @@
+//! Decomp-style output for a set of lists: a source file for each list, plus
+//! * `dynlist_macros.h` (and `dynlist_macros.inc` for gas lists) with the command macros
+//! * `dynlists.h` with an `extern` declaration for every list
+//! * `dynlists.mk`, a Makefile include with the sources, objects, and original ROM ranges
+//! * `dynlists.ld`, a linker script fragment that places each list's object
+
+use asm;
+use c89;
+use dump::{self, Format};
+use dynlist::{DynListIter, Limits, CMD_SIZE};
+use error::Error;
+use std::cell::Cell;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Read, Seek, Write};
+use std::path::Path;
+
+pub const C_HEADER: &str = "dynlist_macros.h";
+pub const GAS_INCLUDE: &str = "dynlist_macros.inc";
+pub const EXTERN_HEADER: &str = "dynlists.h";
+pub const MAKE_INCLUDE: &str = "dynlists.mk";
+pub const LINKER_FRAGMENT: &str = "dynlists.ld";
+
+/// A list that was written into a split directory
+#[derive(Debug, Clone)]
+pub struct SplitList {
+    pub name: String,
+    /// File offsets of the first command and just past the `StopList`
+    pub start: u64,
+    pub end: u64,
+    pub format: Format,
+}
+
+impl SplitList {
+    /// Name of the list's source file
+    pub fn file_name(&self) -> String {
+        format!("{}.{}", self.name, self.format.extension())
+    }
+}
+
+/// Write the list at `cfg.address` into its own source file in `dir`, named after `cfg.label()`.
+/// The directory is made if it isn't there, and the file is removed if the list can't be written.
+pub fn write_list<R>(rdr: R, dir: &Path, cfg: &dump::Config, format: Format, limits: Limits) -> Result<SplitList, Error>
+    where R: Read + Seek
+{
+    let (name, offset) = (cfg.label(), cfg.address);
+    let mut list = SplitList { name: name.clone(), start: offset, end: offset, format };
+    let path = dir.join(list.file_name());
+    fs::create_dir_all(dir).map_err(|err| Error::File { path: dir.display().to_string(), err })?;
+    let count = Cell::new(0);
+    let dynlist = DynListIter::from_reader(rdr, offset)?
+        .limits(limits)
+        .inspect(|_| count.set(count.get() + 1));
+    let mut wtr = File::create(&path)
+        .map(BufWriter::new)
+        .map_err(|err| Error::File { path: path.display().to_string(), err })?;
+    let result = write_prelude(&mut wtr, &name, offset, format)
+        .map_err(Error::from)
+        .and_then(|_| dump::write(format, &mut wtr, dynlist, cfg))
+        .and_then(|_| Ok(wtr.flush()?));
+    if let Err(e) = result {
+        let _ = fs::remove_file(&path);
+        return Err(e);
+    }
+    list.end = offset + count.get() as u64 * CMD_SIZE;
+    Ok(list)
+}
+
+/// Write the lines that go before a list in its own source file
+pub fn write_prelude<W: Write>(mut w: W, name: &str, start: u64, format: Format) -> io::Result<()> {
+    match format {
+        Format::C => {
+            writeln!(w, "/* {}: ROM {:#X} */", name, start)?;
+            writeln!(w, "#include \"{}\"", C_HEADER)?;
+            writeln!(w, "#include \"{}\"\n", EXTERN_HEADER)?;
+        },
+        Format::Gas => {
+            writeln!(w, "# {}: ROM {:#X}", name, start)?;
+            writeln!(w, ".include \"{}\"\n", GAS_INCLUDE)?;
+            writeln!(w, ".section .data")?;
+            writeln!(w, ".balign 4")?;
+            writeln!(w, ".global {}", name)?;
+        },
+        _ => return Err(io::Error::new(io::ErrorKind::InvalidInput,
+            "only C and gas lists can be split into source files")),
+    }
+    Ok(())
+}
+
+/// Write the shared headers, Makefile include, and linker fragment for every list in `dir`
+pub fn write_support_files(dir: &Path, lists: &[SplitList]) -> io::Result<()> {
+    c89::write_header(create(dir, C_HEADER)?)?;
+    if lists.iter().any(|l| l.format == Format::Gas) {
+        asm::write_macros(create(dir, GAS_INCLUDE)?)?;
+    }
+    write_externs(create(dir, EXTERN_HEADER)?, lists)?;
+    write_make_include(create(dir, MAKE_INCLUDE)?, dir, lists)?;
+    write_linker_fragment(create(dir, LINKER_FRAGMENT)?, lists)?;
+    Ok(())
+}
+
+fn create(dir: &Path, name: &str) -> io::Result<BufWriter<File>> {
+    File::create(dir.join(name)).map(BufWriter::new)
+}
+
+fn write_externs<W: Write>(mut w: W, lists: &[SplitList]) -> io::Result<()> {
+    writeln!(w, "#ifndef _DYNLISTS_H_\n#define _DYNLISTS_H_\n")?;
+    writeln!(w, "#include \"{}\"\n", C_HEADER)?;
+    for list in lists {
+        writeln!(w, "extern {} {}[];", c89::STRUCT_NAME, list.name)?;
+    }
+    writeln!(w, "\n#endif /* _DYNLISTS_H_ */")?;
+    Ok(())
+}
+
+fn write_make_include<W: Write>(mut w: W, dir: &Path, lists: &[SplitList]) -> io::Result<()> {
+    writeln!(w, "# Dynlist sources and the ROM ranges they were dumped from")?;
+    writeln!(w, "DYNLIST_DIR := {}\n", dir.display())?;
+    writeln!(w, "DYNLIST_SRCS := \\")?;
+    for list in lists {
+        writeln!(w, "  $(DYNLIST_DIR)/{} \\", list.file_name())?;
+    }
+    writeln!(w)?;
+    writeln!(w, "DYNLIST_OBJS := $(addsuffix .o,$(basename $(DYNLIST_SRCS)))\n")?;
+    for list in lists {
+        writeln!(w, "{}_ROM_START := {:#X}", list.name, list.start)?;
+        writeln!(w, "{}_ROM_END := {:#X}", list.name, list.end)?;
+    }
+    Ok(())
+}
+
+fn write_linker_fragment<W: Write>(mut w: W, lists: &[SplitList]) -> io::Result<()> {
+    writeln!(w, "/* Dynlist objects, in the order of their original ROM ranges */")?;
+    let mut sorted: Vec<&SplitList> = lists.iter().collect();
+    sorted.sort_by_key(|l| l.start);
+    for list in sorted {
+        writeln!(w, "/* {}: {:#X} - {:#X} ({:#x} bytes) */", list.name, list.start, list.end, list.end - list.start)?;
+        // ld matches the path of the input file, which the make include puts under $(DYNLIST_DIR)
+        writeln!(w, "*/{}.o(.data*);", list.name)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,244 @@
+use dynlist::{DObjType, Vector};
+use error::Error;
+use mesh::{Mesh, Vertex};
+use scene::{Scene, Obj};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::Path;
+
+/// Write a Wavefront OBJ with the vertices and faces that a dynlist makes
+pub fn write_obj<W: Write>(mut w: W, scene: &Scene, name: &str, mtllib: Option<&str>) -> io::Result<()> {
+    writeln!(w, "# {} exported by dynlistdump", name)?;
+    if let Some(lib) = mtllib {
+        writeln!(w, "mtllib {}", lib)?;
+    }
+
+    let mut pos = HashMap::new();
+    let mut uv = HashMap::new();
+    let mut normals = HashMap::new();
+    let vertices: Vec<(usize, &Obj)> = scene.of_kind(DObjType::D_VERTEX).collect();
+    for &(i, vtx) in &vertices {
+        let p = vtx.position().unwrap_or(Vector::ZERO);
+        writeln!(w, "v {:?} {:?} {:?}", p.x, p.y, p.z)?;
+        pos.insert(i, pos.len() + 1);
+    }
+    for &(i, vtx) in &vertices {
+        if let Some((s, t)) = vtx.st {
+            writeln!(w, "vt {:?} {:?}", s, t)?;
+            uv.insert(i, uv.len() + 1);
+        }
+    }
+    for &(i, vtx) in &vertices {
+        if let Some(n) = vtx.normal {
+            writeln!(w, "vn {:?} {:?} {:?}", n.x, n.y, n.z)?;
+            normals.insert(i, normals.len() + 1);
+        }
+    }
+
+    let mut cur_group = None;
+    let mut cur_mtl = None;
+    for (i, _) in scene.of_kind(DObjType::D_FACE) {
+        let group = scene.parent_group(i);
+        if group != cur_group {
+            if let Some(g) = group {
+                writeln!(w, "g {}", scene.objs[g].name(g))?;
+            }
+            cur_group = group;
+        }
+        let mtl = scene.face_material(i);
+        if mtl != cur_mtl {
+            match mtl {
+                Some(m) => writeln!(w, "usemtl {}", scene.objs[m].name(m))?,
+                // with no name, the rest of the faces go back to the default material
+                None => writeln!(w, "usemtl")?,
+            }
+            cur_mtl = mtl;
+        }
+
+        let face = match scene.face_vertices(i) {
+            Ok(face) => face,
+            Err(v) => {
+                writeln!(w, "# {} has out of range vertex {}", scene.objs[i].name(i), v)?;
+                continue;
+            },
+        };
+        let elems: Vec<String> = face.iter()
+            .map(|v| match (uv.get(v), normals.get(v)) {
+                (None, None)       => format!("{}", pos[v]),
+                (Some(t), None)    => format!("{}/{}", pos[v], t),
+                (None, Some(n))    => format!("{}//{}", pos[v], n),
+                (Some(t), Some(n)) => format!("{}/{}/{}", pos[v], t, n),
+            })
+            .collect();
+        if elems.len() >= 3 {
+            writeln!(w, "f {}", elems.join(" "))?;
+        }
+    }
+    Ok(())
+}
+
+/// Write the Wavefront MTL library for the materials that a dynlist makes
+pub fn write_mtl<W: Write>(mut w: W, scene: &Scene, name: &str) -> io::Result<()> {
+    writeln!(w, "# {} materials exported by dynlistdump", name)?;
+    for (i, mtl) in scene.of_kind(DObjType::D_MATERIAL) {
+        writeln!(w, "\nnewmtl {}", mtl.name(i))?;
+        if let Some(a) = mtl.ambient {
+            writeln!(w, "Ka {:?} {:?} {:?}", a.x, a.y, a.z)?;
+        }
+        if let Some(d) = mtl.diffuse {
+            writeln!(w, "Kd {:?} {:?} {:?}", d.x, d.y, d.z)?;
+        }
+    }
+    Ok(())
+}
+
+/// Read the mesh in a Wavefront OBJ. Material libraries are looked for relative to `dir`.
+/// Each distinct position/texture/normal combination used by a face becomes its own vertex.
+pub fn read_obj<R: BufRead>(rdr: R, dir: &Path) -> Result<Mesh, Error> {
+    let mut mesh = Mesh::default();
+    let (mut pos, mut uv, mut normals) = (Vec::new(), Vec::new(), Vec::new());
+    let mut used = HashMap::new();
+    let mut mtl = None;
+
+    for (i, line) in rdr.lines().enumerate() {
+        let line = line?;
+        let line = line.split('#').next().unwrap_or_default();
+        let mut words = line.split_whitespace();
+        let keyword = match words.next() {
+            Some(k) => k,
+            None => continue,
+        };
+        let words: Vec<&str> = words.collect();
+        let at_line = |msg: String| Error::Syntax { what: "OBJ file", line: Some(i + 1), msg };
+        match keyword {
+            "v"  => pos.push(parse_vector(&words).map_err(at_line)?),
+            "vn" => normals.push(parse_vector(&words).map_err(at_line)?),
+            "vt" => {
+                let st = parse_floats(&words, 2).map_err(at_line)?;
+                uv.push((st[0], st[1]));
+            },
+            "f" => {
+                let mut face = Vec::new();
+                for w in &words {
+                    let key = parse_face_vertex(w, pos.len(), uv.len(), normals.len()).map_err(at_line)?;
+                    let next = mesh.vertices.len() as u32;
+                    let v = *used.entry(key).or_insert(next);
+                    if v == next {
+                        mesh.vertices.push(Vertex {
+                            pos: pos[key.0],
+                            st: key.1.map(|t| uv[t]),
+                            normal: key.2.map(|n| normals[n]),
+                        });
+                    }
+                    face.push(v);
+                }
+                if face.len() < 3 { return Err(at_line(format!("face has only {} vertices", face.len()))); }
+                mesh.add_polygon(&face, mtl);
+            },
+            "usemtl" => mtl = words.first().map(|name| mesh.material_named(name)),
+            "mtllib" => for lib in &words {
+                let f = File::open(dir.join(lib))
+                    .map_err(|err| Error::File { path: lib.to_string(), err })?;
+                read_mtl(BufReader::new(f), &mut mesh).map_err(|e| match e {
+                    Error::Io(err) => Error::File { path: lib.to_string(), err },
+                    Error::Syntax { what, line, msg } => Error::Syntax { what, line, msg: format!("{}: {}", lib, msg) },
+                    e => e,
+                })?;
+            },
+            _ => (),
+        }
+    }
+    Ok(mesh)
+}
+
+/// Read the ambient and diffuse colours of the materials in a Wavefront MTL library
+pub fn read_mtl<R: BufRead>(rdr: R, mesh: &mut Mesh) -> Result<(), Error> {
+    let mut cur = None;
+    for (i, line) in rdr.lines().enumerate() {
+        let line = line?;
+        let line = line.split('#').next().unwrap_or_default();
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let at_line = |msg: String| Error::Syntax { what: "MTL library", line: Some(i + 1), msg };
+        match words.split_first() {
+            Some((&"newmtl", name)) => cur = Some(mesh.material_named(&name.join(" "))),
+            Some((&"Ka", rgb)) | Some((&"Kd", rgb)) => {
+                let colour = parse_vector(rgb).map_err(at_line)?;
+                let mtl = &mut mesh.materials[cur.ok_or_else(|| at_line("colour before newmtl".to_string()))?];
+                if words[0] == "Ka" { mtl.ambient = Some(colour) } else { mtl.diffuse = Some(colour) }
+            },
+            _ => (),
+        }
+    }
+    Ok(())
+}
+
+fn parse_floats(words: &[&str], count: usize) -> Result<Vec<f32>, String> {
+    if words.len() < count {
+        return Err(format!("expected {} numbers, found {}", count, words.len()));
+    }
+    words[..count].iter()
+        .map(|w| w.parse().map_err(|_| format!("expected a number, found `{}`", w)))
+        .collect()
+}
+
+fn parse_vector(words: &[&str]) -> Result<Vector, String> {
+    let v = parse_floats(words, 3)?;
+    Ok(Vector { x: v[0], y: v[1], z: v[2] })
+}
+
+/// Zero-based position, texture, and normal indices of a face vertex like `1/2/3` or `-1//2`
+fn parse_face_vertex(word: &str, npos: usize, nuv: usize, nnormals: usize)
+    -> Result<(usize, Option<usize>, Option<usize>), String>
+{
+    let index = |part: Option<&str>, len: usize| -> Result<Option<usize>, String> {
+        let part = match part.filter(|p| !p.is_empty()) {
+            Some(p) => p,
+            None => return Ok(None),
+        };
+        let n: i64 = part.parse().map_err(|_| format!("bad face vertex `{}`", word))?;
+        let idx = if n < 0 { len as i64 + n } else { n - 1 };
+        if idx < 0 || idx >= len as i64 {
+            return Err(format!("face vertex `{}` is out of range", word));
+        }
+        Ok(Some(idx as usize))
+    };
+    let mut parts = word.split('/');
+    let pos = index(parts.next(), npos)?.ok_or_else(|| format!("bad face vertex `{}`", word))?;
+    Ok((pos, index(parts.next(), nuv)?, index(parts.next(), nnormals)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dynlist::{DynCmd, DynId, DynListItem, Ptr, PtrParam};
+    use dynlist::DynCmd::*;
+
+    #[test]
+    fn face_without_a_material_resets_it() {
+        let mut cmds = vec![Start, UseIntId(true), MakeObj(DObjType::D_MATERIAL, DynId(1)), SetId(5)];
+        for i in 0..3 {
+            cmds.push(MakeVtx(Vector { x: i as f32, y: 0.0, z: 0.0 }));
+        }
+        for &(id, mtl) in &[(2, Some(5)), (3, None)] {
+            cmds.push(MakeObj(DObjType::D_FACE, DynId(id)));
+            cmds.extend(mtl.map(|n| SetMtl(Ptr::NULL, n)));
+            for v in 0..3 {
+                cmds.push(ParamPtr(PtrParam::PARAM_OBJ_VTX, Ptr(v)));
+            }
+        }
+        cmds.push(Stop);
+        let items = cmds.into_iter().map(|c: DynCmd| Ok(DynListItem::from_cmd(c)));
+        let scene = Scene::from_items(items, 0).unwrap();
+
+        let mut out = Vec::new();
+        write_obj(&mut out, &scene, "test", None).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        let faces: Vec<&str> = text.lines().skip_while(|l| !l.starts_with("usemtl")).collect();
+        assert_eq!(faces, ["usemtl material_1", "f 1 2 3", "usemtl", "f 1 2 3"]);
+
+        let mesh = read_obj(text.as_bytes(), Path::new(".")).unwrap();
+        let mtls: Vec<Option<usize>> = mesh.faces.iter().map(|f| f.material).collect();
+        assert_eq!(mtls, [Some(0), None]);
+    }
+}
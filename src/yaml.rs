@@ -0,0 +1,351 @@
+//! A hand-editable dynlist format, written as a subset of YAML:
+//! ```yaml
+//! # list_80170000
+//! - StartList
+//! - MakeDynObj: [D_JOINT, &id_1 0x1]   # the jaw
+//! - SetScale: [1.0, 1.0, 1.0]
+//! - UseObj: *id_1
+//! - StopList
+//! ```
+//! Every command is a list entry, with its macro arguments as a single value or flow sequence.
+//! Object ids get an anchor where they're first used, and aliases afterwards.
+//! Comments are kept with the command that follows (or ends) them, so they survive a round-trip.
+
+use dynlist::{self, DynCmd, DynListItem, DObjType, ArgVal, CMD_SIZE};
+use error::Error;
+use scene::Interp;
+use std::collections::{HashMap, HashSet};
+use std::io::{self, BufRead, Write};
+
+/// Name used for commands that are written as their six raw words
+const RAW_CMD: &str = "Raw";
+
+/// A YAML anchor (`&name`) or alias (`*name`) on a command argument
+#[derive(Debug, Clone)]
+enum Label {
+    Anchor(String),
+    Alias(String),
+}
+
+/// A single command along with its comments
+#[derive(Debug)]
+struct Entry {
+    /// Full line comments above the command
+    comments: Vec<String>,
+    item: DynListItem,
+    /// Anchor or alias for each macro argument
+    labels: Vec<Option<Label>>,
+    /// Comment at the end of the command's line
+    comment: Option<String>,
+}
+
+/// A dynlist in the YAML format
+#[derive(Debug, Default)]
+pub struct Document {
+    entries: Vec<Entry>,
+    /// Comments after the last command
+    trailing: Vec<String>,
+}
+
+impl Document {
+    /// Build a document from decoded commands, anchoring each object id at its first use
+    pub fn from_items<I>(items: I, label: &str) -> Self
+        where I: IntoIterator<Item = DynListItem>
+    {
+        let mut seen = HashSet::new();
+        let mut entries = Vec::new();
+        for item in items {
+            let mut labels = Vec::new();
+            for arg in item.cmd().args() {
+                match arg {
+                    ArgVal::Id(id) if !id.is_null() => {
+                        let name = format!("id_{:x}", id.0);
+                        labels.push(Some(if seen.insert(id) { Label::Anchor(name) } else { Label::Alias(name) }));
+                    },
+                    ArgVal::Vec(..) => labels.extend(vec![None; 3]),
+                    _ => labels.push(None),
+                }
+            }
+            let comments = if entries.is_empty() { vec![format!(" {}", label)] } else { Vec::new() };
+            entries.push(Entry { comments, item, labels, comment: None });
+        }
+        Document { entries, trailing: Vec::new() }
+    }
+
+    /// Set the comment at the end of a command's line
+    pub fn comment(&mut self, index: usize, text: &str) {
+        if let Some(entry) = self.entries.get_mut(index) {
+            entry.comment = Some(format!(" {}", text));
+        }
+    }
+
+    pub fn into_items(self) -> Vec<DynListItem> {
+        self.entries.into_iter().map(|e| e.item).collect()
+    }
+
+    /// Parse a YAML dynlist. Each command's `DynArg` determines how many arguments it takes,
+    /// and which of those should be integers or floats.
+    pub fn parse<R: BufRead>(rdr: R) -> Result<Self, Error> {
+        let mut doc = Document::default();
+        let mut anchors = HashMap::new();
+        let mut comments = Vec::new();
+
+        for (i, line) in rdr.lines().enumerate() {
+            let line = line?;
+            let lineno = i + 1;
+            let (code, comment) = split_comment(&line);
+            let code = code.trim();
+            if code.is_empty() || code == "---" || code == "..." {
+                comments.extend(comment.map(String::from));
+                continue;
+            }
+            let index = doc.entries.len();
+            let offset = index as u64 * CMD_SIZE;
+            let (raw, labels) = parse_entry(code, &mut anchors)
+                .map_err(|msg| Error::BadCmd { offset, index, line: Some(lineno), msg })?;
+            let item = DynListItem::decode(raw, offset, index)?;
+            doc.entries.push(Entry {
+                comments: comments.split_off(0),
+                item,
+                labels,
+                comment: comment.map(String::from),
+            });
+        }
+        doc.trailing = comments;
+        Ok(doc)
+    }
+
+    /// Write out the document. Flags and types are named by following the current object.
+    pub fn write<W: Write>(&self, mut w: W) -> io::Result<()> {
+        let mut interp = Interp::default();
+        for entry in &self.entries {
+            for c in &entry.comments {
+                writeln!(w, "#{}", c)?;
+            }
+            interp.step(entry.item.cmd());
+            let obj = interp.current().and_then(|(_, obj)| obj.kind);
+            write_entry(&mut w, entry, obj)?;
+            if let Some(ref c) = entry.comment {
+                write!(w, "  #{}", c)?;
+            }
+            writeln!(w)?;
+        }
+        for c in &self.trailing {
+            writeln!(w, "#{}", c)?;
+        }
+        Ok(())
+    }
+}
+
+fn write_entry<W: Write>(w: &mut W, entry: &Entry, obj: Option<DObjType>) -> io::Result<()> {
+    let cmd = entry.item.cmd();
+    let (name, operands) = if entry.item.is_unk() {
+        (RAW_CMD, entry.item.raw().iter().map(|r| format!("{:#x}", r)).collect())
+    } else {
+        let named = cmd.symbolic_arg(obj);
+        let operands = cmd.args().iter().enumerate().flat_map(|(i, arg)| match named {
+            Some((at, ref name)) if at == i => vec![name.clone()],
+            _ => operand_text(arg),
+        }).collect::<Vec<_>>();
+        (entry.item.info().base, operands)
+    };
+    let operands: Vec<String> = operands.into_iter()
+        .enumerate()
+        .map(|(i, op)| match entry.labels.get(i) {
+            Some(Some(Label::Anchor(a))) => format!("&{} {}", a, op),
+            Some(Some(Label::Alias(a)))  => format!("*{}", a),
+            _ => op,
+        })
+        .collect();
+
+    match operands.len() {
+        0 => write!(w, "- {}", name),
+        1 => write!(w, "- {}: {}", name, operands[0]),
+        _ => write!(w, "- {}: [{}]", name, operands.join(", ")),
+    }
+}
+
+/// Text for each macro argument that a decoded argument fills
+fn operand_text(arg: &ArgVal) -> Vec<String> {
+    use self::ArgVal::*;
+    match arg {
+        Bool(b)   => vec![if *b { "TRUE" } else { "FALSE" }.to_string()],
+        Int(n)    => vec![n.to_string()],
+        Flag(n)   => vec![format!("{:#x}", n)],
+        Float(f)  => vec![format!("{:?}", f)],
+        Vec(v)    => vec![format!("{:?}", v.x), format!("{:?}", v.y), format!("{:?}", v.z)],
+        Id(id)    => vec![format!("{:#x}", id.0)],
+        Ptr(p)    => vec![format!("{:#010x}", p.0)],
+        Obj(t)    => vec![t.to_string()],
+        Param(p)  => vec![p.to_string()],
+    }
+}
+
+/// Split a line at the start of its comment. A `#` only starts a comment at the
+/// beginning of a line or after whitespace, like YAML.
+fn split_comment(line: &str) -> (&str, Option<&str>) {
+    let mut prev = ' ';
+    for (i, c) in line.char_indices() {
+        if c == '#' && prev.is_whitespace() {
+            return (&line[..i], Some(line[i + 1..].trim_end()));
+        }
+        prev = c;
+    }
+    (line, None)
+}
+
+type Parsed = ([u32; 6], Vec<Option<Label>>);
+
+fn parse_entry(code: &str, anchors: &mut HashMap<String, String>) -> Result<Parsed, String> {
+    let body = match code.strip_prefix('-') {
+        Some(b) if b.is_empty() || b.starts_with(char::is_whitespace) => b.trim(),
+        _ => return Err(format!("expected a `- Command` list entry, found `{}`", code)),
+    };
+    let (name, rest) = match body.find(':') {
+        Some(i) => (body[..i].trim(), body[i + 1..].trim()),
+        None => (body, ""),
+    };
+    let tokens: Vec<&str> = if rest.starts_with('[') {
+        let inner = rest.strip_suffix(']')
+            .ok_or_else(|| format!("unterminated argument list `{}`", rest))?;
+        inner[1..].split(',').map(str::trim).filter(|t| !t.is_empty()).collect()
+    } else if rest.is_empty() {
+        Vec::new()
+    } else {
+        vec![rest]
+    };
+
+    let mut labels = Vec::new();
+    let mut values = Vec::new();
+    for tok in tokens {
+        let (label, value) = if let Some(alias) = tok.strip_prefix('*') {
+            let value = anchors.get(alias)
+                .ok_or_else(|| format!("alias `*{}` used before its anchor", alias))?
+                .clone();
+            (Some(Label::Alias(alias.to_string())), value)
+        } else if let Some(anchored) = tok.strip_prefix('&') {
+            let mut parts = anchored.splitn(2, char::is_whitespace);
+            let anchor = parts.next().unwrap_or_default().to_string();
+            let value = parts.next().map(str::trim).unwrap_or_default().to_string();
+            if value.is_empty() { return Err(format!("anchor `&{}` has no value", anchor)); }
+            anchors.insert(anchor.clone(), value.clone());
+            (Some(Label::Anchor(anchor)), value)
+        } else {
+            (None, tok.to_string())
+        };
+        labels.push(label);
+        values.push(value);
+    }
+
+    let mut raw = [0; 6];
+    if name == RAW_CMD {
+        if values.len() != 6 { return Err(format!("{} takes 6 words, found {}", RAW_CMD, values.len())); }
+        for (r, v) in raw.iter_mut().zip(&values) {
+            *r = dynlist::parse_int(v).ok_or_else(|| format!("expected an integer word, found `{}`", v))?;
+        }
+    } else {
+        let info = DynCmd::lookup(name).ok_or_else(|| format!("unknown command `{}`", name))?;
+        let (slots, params) = (info.kind.slots(), info.kind.params());
+        if values.len() != slots.len() {
+            return Err(format!("{} takes {} argument(s) ({}), found {}",
+                name, slots.len(), params.join(", "), values.len()));
+        }
+        raw[0] = info.id;
+        for ((&slot, param), v) in slots.iter().zip(params).zip(&values) {
+            raw[slot] = if slot < 3 {
+                dynlist::parse_int_expr(v)
+                    .ok_or_else(|| format!("{} expects an integer for `{}`, found `{}`", name, param, v))?
+            } else {
+                v.parse::<f32>()
+                    .map(f32::to_bits)
+                    .map_err(|_| format!("{} expects a float for `{}`, found `{}`", name, param, v))?
+            };
+        }
+    }
+    Ok((raw, labels))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dynlist::{DynId, NetType, Vector};
+    use dynlist::DynCmd::*;
+
+    fn written(doc: &Document) -> String {
+        let mut out = Vec::new();
+        doc.write(&mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    fn items(cmds: Vec<DynCmd>) -> Vec<DynListItem> {
+        cmds.into_iter().map(DynListItem::from_cmd).collect()
+    }
+
+    #[test]
+    fn round_trip() {
+        let cmds = vec![
+            Start,
+            UseIntId(true),
+            MakeObj(DObjType::D_NET, DynId(1)),
+            SetType(NetType::NET_TYPE_VERTICES as u32),
+            SetHeaderFlag(0x06),
+            SetRotation(Vector { x: 0.1, y: -2.5, z: 1e-3 }),
+            MakeObj(DObjType::D_JOINT, DynId(2)),
+            Attach(DynId(1)),
+            UseObj(DynId(2)),
+            SetColourNum(2),
+            Stop,
+        ];
+        let expected: Vec<[u32; 6]> = cmds.iter().map(|c| c.to_struct()).collect();
+        let mut doc = Document::from_items(items(cmds), "list_80170000");
+        doc.comment(2, "the net");
+        let text = written(&doc);
+        assert!(text.contains("- MakeDynObj: [D_NET, &id_1 0x1]  # the net\n"));
+        assert!(text.contains("- SetType: NET_TYPE_VERTICES\n"));
+        assert!(text.contains("- SetHeaderFlag: OBJ_INVISIBLE | OBJ_PICKED\n"));
+        assert!(text.contains("- Attach: *id_1\n- UseObj: *id_2\n"));
+        assert!(text.contains("- SetColourNum: COLOUR_RED\n"));
+
+        let parsed = Document::parse(text.as_bytes()).unwrap();
+        assert_eq!(written(&parsed), text);
+        let raw: Vec<[u32; 6]> = parsed.into_items().iter().map(|i| *i.raw()).collect();
+        assert_eq!(raw, expected);
+    }
+
+    #[test]
+    fn hand_written_anchors_and_comments() {
+        let text = "\
+---
+# the jaw
+- MakeDynObj: [D_JOINT, &jaw 0x10]
+- SetFlag: 0x4 | 0x1   # bits
+- UseObj: *jaw
+# end
+";
+        let doc = Document::parse(text.as_bytes()).unwrap();
+        assert_eq!(written(&doc), "\
+# the jaw
+- MakeDynObj: [D_JOINT, &jaw 0x10]
+- SetFlag: 0x5  # bits
+- UseObj: *jaw
+# end
+");
+        let cmds: Vec<DynCmd> = doc.into_items().iter().map(|i| *i.cmd()).collect();
+        assert_eq!(cmds, [MakeObj(DObjType::D_JOINT, DynId(0x10)), SetFlag(5), UseObj(DynId(0x10))]);
+    }
+
+    #[test]
+    fn errors_have_their_line() {
+        let bad = |text: &str| match Document::parse(text.as_bytes()) {
+            Err(Error::BadCmd { index, line, msg, .. }) => (index, line, msg),
+            other => panic!("expected a bad command, got {:?}", other),
+        };
+        let (index, line, msg) = bad("- StartList\n\n- UseObj: *jaw\n");
+        assert_eq!((index, line), (1, Some(3)));
+        assert!(msg.contains("before its anchor"), "{}", msg);
+        let (_, _, msg) = bad("- SetScale: [1.0, 2.0]\n");
+        assert!(msg.contains("takes 3 argument(s)"), "{}", msg);
+        let (_, _, msg) = bad("- SetNothing\n");
+        assert!(msg.contains("unknown command"), "{}", msg);
+    }
+}